@@ -1,4 +1,7 @@
+use std::{error::Error, fmt, str::FromStr};
+
 use cairo::Context;
+use serde::de;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Color {
@@ -39,6 +42,23 @@ impl Color {
         0.2126 * r + 0.7152 * g + 0.0722 * b
     }
 
+    pub fn lerp(&self, o: &Color, t: f64) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f64 * (1.0 - t) + b as f64 * t).round() as u8;
+        Color::from_rgb(mix(self.r, o.r), mix(self.g, o.g), mix(self.b, o.b))
+    }
+
+    pub fn from_arg(s: &str) -> Result<Self, String> {
+        s.parse().map_err(|_| format!("invalid color: {}", s))
+    }
+
+    pub fn distance_to(&self, o: &Color) -> f64 {
+        let dr = (self.r as f64 - o.r as f64).abs();
+        let dg = (self.g as f64 - o.g as f64).abs();
+        let db = (self.b as f64 - o.b as f64).abs();
+        dr.max(dg).max(db)
+    }
+
     pub fn from_rgb_u32(c: u32) -> Self {
         Self::from_rgb(
             ((c >> 16) & 0xff) as u8,
@@ -53,3 +73,72 @@ impl std::fmt::Display for Color {
         write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
     }
 }
+
+impl FromStr for Color {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            return match hex.len() {
+                6 => Ok(Self::from_rgb_u32(u32::from_str_radix(hex, 16)?)),
+                3 => {
+                    let c = u32::from_str_radix(hex, 16)?;
+                    let (r, g, b) = ((c >> 8) & 0xf, (c >> 4) & 0xf, c & 0xf);
+                    Ok(Self::from_rgb_u32(
+                        (r * 0x11) << 16 | (g * 0x11) << 8 | (b * 0x11),
+                    ))
+                }
+                _ => Err(format!("invalid color: {}", s).into()),
+            };
+        }
+
+        let args = s
+            .strip_prefix("rgb(")
+            .and_then(|s| s.strip_suffix(')'))
+            .ok_or(format!("invalid color: {}", s))?;
+        let mut parts = args.split(',').map(parse_channel);
+        let r = parts.next().ok_or("no r")??;
+        let g = parts.next().ok_or("no g")??;
+        let b = parts.next().ok_or("no b")??;
+        if parts.next().is_some() {
+            return Err(format!("invalid color: {}", s).into());
+        }
+        Ok(Self::from_rgb(r, g, b))
+    }
+}
+
+fn parse_channel(s: &str) -> Result<u8, Box<dyn Error>> {
+    let s = s.trim();
+    let v = match s.strip_suffix('%') {
+        Some(p) => p.trim().parse::<f64>()? * 255.0 / 100.0,
+        None => s.parse::<f64>()?,
+    };
+    Ok(v.round().clamp(0.0, 255.0) as u8)
+}
+
+impl<'d> de::Deserialize<'d> for Color {
+    fn deserialize<D>(de: D) -> Result<Color, D::Error>
+    where
+        D: de::Deserializer<'d>,
+    {
+        de.deserialize_str(ColorVisitor)
+    }
+}
+
+struct ColorVisitor;
+
+impl<'d> de::Visitor<'d> for ColorVisitor {
+    type Value = Color;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a color string")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Color::from_str(v).map_err(|e| E::custom(e.to_string()))
+    }
+}
@@ -0,0 +1,375 @@
+use std::{error::Error, fmt, fs, io::BufReader, path, str::FromStr};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use lazy_static::lazy_static;
+use regex::Regex;
+use tiff::{decoder::DecodingResult, tags::Tag, ColorType};
+
+use crate::{extract, geo, grid::Grid, Point, Size};
+
+lazy_static! {
+    static ref HGT_NAME_PATTERN: Regex = Regex::new(r"(?i)^([NS])(\d{2})([EW])(\d{3})").unwrap();
+}
+
+// GeoTIFF keys.
+const GT_RASTER_TYPE: u16 = 1025;
+const PROJECTED_CS_TYPE: u16 = 3072;
+const RASTER_PIXEL_IS_POINT: u16 = 2;
+
+// SRTM marks voids with the smallest i16, and grids derived from it often keep
+// that value without declaring it, so it's treated as missing unless the file
+// names a different nodata value.
+const SRTM_VOID: f64 = i16::MIN as f64;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap()]
+    src: String,
+
+    #[clap()]
+    dst: String,
+
+    #[clap(long, value_parser=DemFormat::from_arg)]
+    format: Option<DemFormat>,
+
+    #[clap(long, default_value_t = 10.0)]
+    interval: f64,
+
+    #[clap(long, default_value_t = 5)]
+    index_every: usize,
+
+    #[clap(long, default_value_t = 4.0)]
+    cell_size: f64,
+
+    #[clap(long, value_parser=geo::Projection::from_arg)]
+    source_projection: Option<geo::Projection>,
+}
+
+impl Args {
+    fn format(&self) -> Result<DemFormat, Box<dyn Error>> {
+        self.format
+            .or_else(|| DemFormat::from_path(&self.src))
+            .ok_or_else(|| format!("unknown format for {}, use --format", self.src).into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DemFormat {
+    Ascii,
+    Hgt,
+    GeoTiff,
+}
+
+impl DemFormat {
+    pub fn from_arg(s: &str) -> Result<Self, String> {
+        s.parse().map_err(|_| format!("invalid dem format: {}", s))
+    }
+
+    pub fn from_path(path: &str) -> Option<Self> {
+        path::Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| match ext.to_ascii_lowercase().as_str() {
+                "asc" => Some(DemFormat::Ascii),
+                "hgt" => Some(DemFormat::Hgt),
+                "tif" | "tiff" => Some(DemFormat::GeoTiff),
+                _ => None,
+            })
+    }
+}
+
+impl FromStr for DemFormat {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ascii" | "asc" => Ok(DemFormat::Ascii),
+            "hgt" | "srtm" => Ok(DemFormat::Hgt),
+            "geotiff" | "tiff" | "tif" => Ok(DemFormat::GeoTiff),
+            _ => Err(format!("unknown dem format: {}", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for DemFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DemFormat::Ascii => write!(f, "ascii"),
+            DemFormat::Hgt => write!(f, "hgt"),
+            DemFormat::GeoTiff => write!(f, "geotiff"),
+        }
+    }
+}
+
+// Elevation samples along with where the first one is, in projected
+// coordinates, and how far apart the columns and rows are. Rows run south.
+// The projection is the EPSG code of the projected coordinate system, or
+// none when the coordinates are latitude and longitude.
+struct Raster {
+    grid: Grid,
+    origin: Point,
+    step: (f64, f64),
+    epsg: Option<u16>,
+}
+
+fn read_ascii(src: &str) -> Result<Raster, Box<dyn Error>> {
+    let text = fs::read_to_string(src)?;
+    let mut tokens = text.split_whitespace().peekable();
+
+    let mut header = std::collections::HashMap::new();
+    while let Some(key) = tokens.next_if(|t| t.starts_with(|c: char| c.is_ascii_alphabetic())) {
+        let value = tokens
+            .next()
+            .ok_or_else(|| format!("no value for {}", key))?
+            .parse::<f64>()?;
+        header.insert(key.to_ascii_lowercase(), value);
+    }
+    let get = |key: &str| {
+        header
+            .get(key)
+            .copied()
+            .ok_or_else(|| format!("no {} in header", key))
+    };
+
+    let (cols, rows) = (get("ncols")? as usize, get("nrows")? as usize);
+    let (dx, dy) = match header.get("cellsize") {
+        Some(&size) => (size, size),
+        None => (get("dx")?, get("dy")?),
+    };
+    let (x, y) = match (header.get("xllcenter"), header.get("yllcenter")) {
+        (Some(&x), Some(&y)) => (x, y),
+        _ => (get("xllcorner")? + dx / 2.0, get("yllcorner")? + dy / 2.0),
+    };
+    let nodata = header.get("nodata_value").copied().unwrap_or(SRTM_VOID);
+
+    let values = tokens
+        .map(|t| {
+            let v = t.parse::<f64>()?;
+            Ok(if v == nodata { f64::NAN } else { v })
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    if values.len() != cols * rows {
+        return Err(format!("expected {} samples, found {}", cols * rows, values.len()).into());
+    }
+
+    Ok(Raster {
+        grid: Grid::new(cols, rows, values),
+        origin: Point::from_xy(x, y + dy * (rows - 1) as f64),
+        step: (dx, dy),
+        epsg: None,
+    })
+}
+
+// SRTM tiles are named for their southwest corner and hold a square of
+// big-endian samples covering one degree, with edges shared by neighbors.
+fn read_hgt(src: &str) -> Result<Raster, Box<dyn Error>> {
+    let name = path::Path::new(src)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    let caps = HGT_NAME_PATTERN
+        .captures(name)
+        .ok_or_else(|| format!("{} is not named for its location, like N35W080.hgt", src))?;
+    let sign = |h: &str, neg: &str| {
+        if h.eq_ignore_ascii_case(neg) {
+            -1.0
+        } else {
+            1.0
+        }
+    };
+    let lat = sign(&caps[1], "S") * caps[2].parse::<f64>()?;
+    let lng = sign(&caps[3], "W") * caps[4].parse::<f64>()?;
+
+    let bytes = fs::read(src)?;
+    let n = ((bytes.len() / 2) as f64).sqrt() as usize;
+    if n < 2 || n * n * 2 != bytes.len() {
+        return Err(format!("{} is not a square tile", src).into());
+    }
+    let mut r = &bytes[..];
+    let values = (0..n * n)
+        .map(|_| {
+            r.read_i16::<BigEndian>()
+                .map(|v| if v == i16::MIN { f64::NAN } else { v as f64 })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let step = 1.0 / (n - 1) as f64;
+    Ok(Raster {
+        grid: Grid::new(n, n, values),
+        origin: Point::from_xy(lng, lat + 1.0),
+        step: (step, step),
+        epsg: None,
+    })
+}
+
+fn read_geotiff(src: &str) -> Result<Raster, Box<dyn Error>> {
+    let mut decoder = tiff::decoder::Decoder::new(BufReader::new(fs::File::open(src)?))?;
+    if !matches!(decoder.colortype()?, ColorType::Gray(_)) {
+        return Err(format!("{} is not a single-band image", src).into());
+    }
+    let (cols, rows) = decoder.dimensions()?;
+
+    let scale = decoder.get_tag_f64_vec(Tag::ModelPixelScaleTag)?;
+    let tiepoint = decoder.get_tag_f64_vec(Tag::ModelTiepointTag)?;
+    if scale.len() < 2 || tiepoint.len() < 6 {
+        return Err("invalid georeferencing tags".into());
+    }
+    let keys = decoder
+        .get_tag_u16_vec(Tag::GeoKeyDirectoryTag)
+        .unwrap_or_default();
+    let key = |id: u16| {
+        keys.chunks(4)
+            .skip(1)
+            .find(|k| k.len() == 4 && k[0] == id && k[1] == 0)
+            .map(|k| k[3])
+    };
+    let nodata = decoder
+        .get_tag_ascii_string(Tag::GdalNodata)
+        .ok()
+        .and_then(|s| s.trim_matches(char::from(0)).trim().parse::<f64>().ok())
+        .unwrap_or(SRTM_VOID);
+
+    // Tiepoints are on the corner of a pixel unless the raster says its
+    // samples are points.
+    let (dx, dy) = (scale[0], scale[1]);
+    let half = if key(GT_RASTER_TYPE) == Some(RASTER_PIXEL_IS_POINT) {
+        0.0
+    } else {
+        0.5
+    };
+    let origin = Point::from_xy(
+        tiepoint[3] + (half - tiepoint[0]) * dx,
+        tiepoint[4] - (half - tiepoint[1]) * dy,
+    );
+
+    let values: Vec<f64> = match decoder.read_image()? {
+        DecodingResult::U8(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::U16(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::U32(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::U64(v) => v.into_iter().map(|v| v as f64).collect(),
+        DecodingResult::F16(v) => v.into_iter().map(|v| v.to_f64()).collect(),
+        DecodingResult::F32(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::F64(v) => v,
+        DecodingResult::I8(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::I16(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::I32(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::I64(v) => v.into_iter().map(|v| v as f64).collect(),
+    };
+    let values = values
+        .into_iter()
+        .map(|v| if v == nodata { f64::NAN } else { v })
+        .collect();
+
+    Ok(Raster {
+        grid: Grid::new(cols as usize, rows as usize, values),
+        origin,
+        step: (dx, dy),
+        epsg: key(PROJECTED_CS_TYPE),
+    })
+}
+
+pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    if args.interval <= 0.0 {
+        return Err("interval must be positive".into());
+    }
+
+    let raster = match args.format()? {
+        DemFormat::Ascii => read_ascii(&args.src)?,
+        DemFormat::Hgt => read_hgt(&args.src)?,
+        DemFormat::GeoTiff => read_geotiff(&args.src)?,
+    };
+    let Raster {
+        grid,
+        origin,
+        step: (dx, dy),
+        epsg,
+    } = raster;
+    let projection = match (args.source_projection, epsg) {
+        (Some(projection), _) => projection,
+        (None, None) => geo::Projection::Equirectangular,
+        (None, Some(code)) => format!("epsg:{}", code).parse().map_err(|_| {
+            format!(
+                "EPSG:{} is not a supported projection, give it with --source-projection",
+                code
+            )
+        })?,
+    };
+    if grid.width() < 2 || grid.height() < 2 {
+        return Err("elevation grid is too small".into());
+    }
+
+    let (cols, rows) = (grid.width() - 1, grid.height() - 1);
+    let nw = projection.unproject(&origin);
+    let se = projection.unproject(&Point::from_xy(
+        origin.x() + dx * cols as f64,
+        origin.y() - dy * rows as f64,
+    ));
+
+    // Degrees of longitude shrink away from the equator, so widen or narrow
+    // the columns to keep the map's aspect true to the ground.
+    let aspect = match projection {
+        geo::Projection::Equirectangular => dx / dy * ((nw.lat + se.lat) / 2.0).to_radians().cos(),
+        _ => dx / dy,
+    };
+    let (cx, cy) = (args.cell_size * aspect, args.cell_size);
+
+    if grid.range().is_none() {
+        return Err("no elevation samples".into());
+    }
+    let contours = grid.to_contours(args.interval, args.index_every, (cx, cy));
+
+    let data = extract::Data::new(
+        Size::new(cols as f64 * cx, rows as f64 * cy),
+        geo::Rect::new(nw, se),
+        projection,
+        None,
+        contours,
+    );
+    println!(
+        "{} contours, size = {}, region = {}, projection = {}",
+        data.contours.len(),
+        data.size,
+        data.region,
+        data.projection
+    );
+
+    serde_json::to_writer(&mut fs::File::create(&args.dst)?, &data)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(name: &str, text: &str) -> Raster {
+        let src = std::env::temp_dir().join(format!("topo-{}-{}.asc", name, std::process::id()));
+        fs::write(&src, text).unwrap();
+        let raster = read_ascii(src.to_str().unwrap());
+        fs::remove_file(&src).unwrap();
+        raster.unwrap()
+    }
+
+    #[test]
+    fn srtm_voids_are_missing_without_a_nodata_value() {
+        let raster = read(
+            "void",
+            "ncols 2\nnrows 2\nxllcorner 0\nyllcorner 0\ncellsize 1\n\
+             100 -32768\n120 -9999\n",
+        );
+        assert!(raster.grid.get(1, 0).is_nan());
+        assert_eq!(raster.grid.get(1, 1), -9999.0);
+        assert_eq!(raster.grid.range(), Some((-9999.0, 120.0)));
+    }
+
+    #[test]
+    fn a_declared_nodata_value_replaces_the_srtm_void() {
+        let raster = read(
+            "nodata",
+            "ncols 2\nnrows 2\nxllcorner 0\nyllcorner 0\ncellsize 1\nNODATA_value -9999\n\
+             100 -32768\n120 -9999\n",
+        );
+        assert_eq!(raster.grid.get(1, 0), -32768.0);
+        assert!(raster.grid.get(1, 1).is_nan());
+    }
+}
@@ -0,0 +1,247 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{
+    extract::{Contour, Kind},
+    Point, Rect,
+};
+
+lazy_static! {
+    static ref LABEL_PATTERN: Regex =
+        Regex::new(r"^\s*(-?\d{1,3}(?:,\d{3})+|-?\d+)(\.\d+)?\s*(?:ft|feet|m|')?\s*$").unwrap();
+}
+
+// Index contours are drawn at every fifth interval.
+pub(crate) const INDEX_EVERY: f64 = 5.0;
+
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub at: Point,
+    pub elevation: f64,
+}
+
+impl Label {
+    pub fn parse(at: Point, text: &str) -> Option<Label> {
+        let caps = LABEL_PATTERN.captures(text)?;
+        let whole = caps.get(1)?.as_str().replace(',', "");
+        let frac = caps.get(2).map_or("", |m| m.as_str());
+        Some(Label {
+            at,
+            elevation: format!("{}{}", whole, frac).parse().ok()?,
+        })
+    }
+}
+
+struct Line {
+    polylines: Vec<Vec<Point>>,
+    bounds: Rect,
+}
+
+impl Line {
+    fn from(contour: &Contour) -> Self {
+        Self {
+            polylines: contour.path.flatten(),
+            bounds: contour.path.bounds(),
+        }
+    }
+
+    fn segments(&self) -> impl Iterator<Item = (&Point, &Point)> {
+        self.polylines
+            .iter()
+            .flat_map(|line| line.windows(2).map(|w| (&w[0], &w[1])))
+    }
+
+    fn nearest(&self, p: &Point) -> Option<(f64, Point)> {
+        self.segments()
+            .map(|(a, b)| {
+                let q = nearest_on_segment(p, a, b);
+                (p.distance_to(&q), q)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    fn crossings(&self, p: &Point, q: &Point) -> usize {
+        if !self
+            .bounds
+            .intersects(&Rect::from_points(p, q).expand(f64::EPSILON))
+        {
+            return 0;
+        }
+        self.segments()
+            .filter(|(a, b)| segments_intersect(p, q, a, b))
+            .count()
+    }
+
+    // A point in the middle of the longest subpath, used as the
+    // contour's representative location.
+    fn sample(&self) -> Option<Point> {
+        let line = self.polylines.iter().max_by_key(|l| l.len())?;
+        line.get(line.len() / 2).copied()
+    }
+}
+
+fn nearest_on_segment(p: &Point, a: &Point, b: &Point) -> Point {
+    let (dx, dy) = (b.x() - a.x(), b.y() - a.y());
+    let len2 = dx * dx + dy * dy;
+    if len2 == 0.0 {
+        return *a;
+    }
+    let t = (((p.x() - a.x()) * dx + (p.y() - a.y()) * dy) / len2).clamp(0.0, 1.0);
+    Point::from_xy(a.x() + t * dx, a.y() + t * dy)
+}
+
+fn cross(o: &Point, a: &Point, b: &Point) -> f64 {
+    (a.x() - o.x()) * (b.y() - o.y()) - (a.y() - o.y()) * (b.x() - o.x())
+}
+
+fn segments_intersect(p: &Point, q: &Point, a: &Point, b: &Point) -> bool {
+    let d1 = cross(a, b, p);
+    let d2 = cross(a, b, q);
+    let d3 = cross(p, q, a);
+    let d4 = cross(p, q, b);
+    ((d1 > 0.0) != (d2 > 0.0)) && ((d3 > 0.0) != (d4 > 0.0))
+}
+
+// Whether v is a whole multiple of of, allowing for rounding in elevations
+// that have been converted between units.
+pub(crate) fn is_multiple(v: f64, of: f64) -> bool {
+    let r = (v / of).round();
+    (v - r * of).abs() < 1e-6 * of.abs().max(1.0)
+}
+
+// Attaches each label to the nearest contour within max_distance, preferring
+// index contours since those are the ones that carry labels. Contours that
+// collect several labels take the most common value.
+pub fn assign_labels(contours: &mut [Contour], labels: &[Label], max_distance: f64) {
+    let lines = contours.iter().map(Line::from).collect::<Vec<_>>();
+    let has_index = contours.iter().any(|c| c.kind == Kind::Index);
+    let mut votes: Vec<Vec<f64>> = vec![Vec::new(); contours.len()];
+
+    for label in labels.iter() {
+        let area = Rect::from_points(&label.at, &label.at).expand(max_distance);
+        let nearest = lines
+            .iter()
+            .enumerate()
+            .filter(|(i, l)| {
+                (!has_index || contours[*i].kind == Kind::Index) && l.bounds.intersects(&area)
+            })
+            .filter_map(|(i, l)| l.nearest(&label.at).map(|(d, _)| (i, d)))
+            .filter(|(_, d)| *d <= max_distance)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, _)) = nearest {
+            votes[i].push(label.elevation);
+        }
+    }
+
+    for (contour, votes) in contours.iter_mut().zip(votes) {
+        let mode = votes
+            .iter()
+            .max_by_key(|&v| votes.iter().filter(|&o| o == v).count())
+            .copied();
+        if let Some(e) = mode {
+            contour.elevation = Some(e);
+        }
+    }
+}
+
+// Guesses the contour interval from the spacing of labeled elevations.
+pub fn infer_interval(contours: &[Contour]) -> Option<f64> {
+    let mut elevations = contours
+        .iter()
+        .filter_map(|c| c.elevation)
+        .collect::<Vec<_>>();
+    elevations.sort_by(|a, b| a.total_cmp(b));
+    elevations.dedup();
+    let step = elevations
+        .windows(2)
+        .map(|w| w[1] - w[0])
+        .filter(|d| *d > 0.0)
+        .min_by(|a, b| a.total_cmp(b))?;
+    let labels_on_index = contours
+        .iter()
+        .any(|c| c.kind == Kind::Index && c.elevation.is_some());
+    Some(if labels_on_index {
+        step / INDEX_EVERY
+    } else {
+        step
+    })
+}
+
+// Fills in elevations for unlabeled contours by counting how many contour
+// lines lie between them and the nearest labeled contour. A second labeled
+// contour with a different elevation decides whether that is uphill or
+// downhill; failing that, the index/intermediate kind is used. Contours that
+// remain ambiguous are left without an elevation.
+pub fn infer_elevations(contours: &mut [Contour], interval: f64) {
+    let lines = contours.iter().map(Line::from).collect::<Vec<_>>();
+    let anchors = contours
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| c.elevation.map(|e| (i, e)))
+        .collect::<Vec<_>>();
+    if anchors.is_empty() {
+        return;
+    }
+    let has_index = contours.iter().any(|c| c.kind == Kind::Index);
+
+    let steps_between = |from: usize, p: &Point, to: usize, q: &Point| {
+        lines
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != from && *j != to)
+            .map(|(_, l)| l.crossings(p, q))
+            .sum::<usize>() as f64
+            + 1.0
+    };
+
+    let mut inferred = Vec::new();
+    for (i, contour) in contours.iter().enumerate() {
+        if contour.elevation.is_some() {
+            continue;
+        }
+        let p = match lines[i].sample() {
+            Some(p) => p,
+            None => continue,
+        };
+
+        let mut near = anchors
+            .iter()
+            .filter_map(|&(j, e)| lines[j].nearest(&p).map(|(d, q)| (j, e, d, q)))
+            .collect::<Vec<_>>();
+        near.sort_by(|a, b| a.2.total_cmp(&b.2));
+        let (a, ea, _, qa) = match near.first() {
+            Some(&n) => n,
+            None => continue,
+        };
+
+        let steps = steps_between(i, &p, a, &qa);
+        let mut candidates = vec![ea + steps * interval, ea - steps * interval];
+        if has_index {
+            let index = contour.kind == Kind::Index;
+            candidates.retain(|&e| is_multiple(e, interval * INDEX_EVERY) == index);
+        }
+
+        if candidates.len() > 1 {
+            if let Some(&(b, eb, _, qb)) = near.iter().find(|n| n.1 != ea) {
+                let steps = steps_between(i, &p, b, &qb);
+                let err = |e: f64| ((e - eb).abs() - steps * interval).abs();
+                let (e0, e1) = (err(candidates[0]), err(candidates[1]));
+                if e0 != e1 {
+                    candidates = vec![if e0 < e1 {
+                        candidates[0]
+                    } else {
+                        candidates[1]
+                    }];
+                }
+            }
+        }
+
+        if let [e] = candidates[..] {
+            inferred.push((i, e));
+        }
+    }
+
+    for (i, e) in inferred {
+        contours[i].elevation = Some(e);
+    }
+}
@@ -0,0 +1,107 @@
+use std::{error::Error, fmt, fs, io::BufWriter, str::FromStr};
+
+use serde_json::{json, Value};
+
+use crate::{extract, geo, Point};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap()]
+    src: String,
+
+    #[clap()]
+    dst: String,
+
+    #[clap(long, value_parser=ExportFormat::from_arg, default_value_t)]
+    format: ExportFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    #[default]
+    GeoJson,
+}
+
+impl ExportFormat {
+    pub fn from_arg(s: &str) -> Result<Self, String> {
+        s.parse()
+            .map_err(|_| format!("invalid export format: {}", s))
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "geojson" => Ok(ExportFormat::GeoJson),
+            _ => Err(format!("unknown export format: {}", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::GeoJson => write!(f, "geojson"),
+        }
+    }
+}
+
+// GeoJSON positions are longitude first.
+fn position(georef: &geo::Georef, p: &Point) -> Value {
+    let ll = georef.to_latlng(p);
+    json!([ll.lng, ll.lat])
+}
+
+fn to_feature(georef: &geo::Georef, contour: &extract::Contour) -> Option<Value> {
+    let mut lines = contour
+        .path
+        .flatten()
+        .into_iter()
+        .filter(|line| line.len() > 1)
+        .map(|line| line.iter().map(|p| position(georef, p)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let geometry = match lines.len() {
+        0 => return None,
+        1 => json!({
+            "type": "LineString",
+            "coordinates": lines.remove(0),
+        }),
+        _ => json!({
+            "type": "MultiLineString",
+            "coordinates": lines,
+        }),
+    };
+
+    Some(json!({
+        "type": "Feature",
+        "geometry": geometry,
+        "properties": {
+            "kind": contour.kind,
+            "elevation": contour.elevation,
+        },
+    }))
+}
+
+pub fn to_geojson(data: &extract::Data) -> Result<Value, Box<dyn Error>> {
+    let georef = data.georef()?;
+    Ok(json!({
+        "type": "FeatureCollection",
+        "features": data
+            .contours
+            .iter()
+            .filter_map(|c| to_feature(&georef, c))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let data = extract::Data::open(&args.src)?;
+    let w = BufWriter::new(fs::File::create(&args.dst)?);
+    match args.format {
+        ExportFormat::GeoJson => serde_json::to_writer(w, &to_geojson(&data)?)?,
+    }
+    Ok(())
+}
@@ -1,9 +1,17 @@
-use std::{error::Error, fs, io::BufReader};
+use std::{error::Error, fs, io::BufReader, sync::OnceLock};
 
 use serde::{Deserialize, Serialize};
-use xml_dom::level2::{convert::*, Document, Element, RefNode};
+use xml_dom::level2::{convert::*, Document, Element, Node, NodeType, RefNode};
 
-use crate::{geo, Path, Point, Rect, Size};
+use crate::{
+    elevation::{self, Label},
+    geo,
+    index::Index,
+    matcher::{self, Match},
+    pack, pdf,
+    tint::Tints,
+    Color, Matcher, Path, Point, Range, Rect, Size, Transform,
+};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -15,6 +23,122 @@ pub struct Args {
 
     #[clap()]
     dst: String,
+
+    #[clap(long, default_value_t = 1)]
+    page: u32,
+
+    #[clap(long, value_parser=geo::Projection::from_arg, default_value_t)]
+    projection: geo::Projection,
+
+    #[clap(long, value_parser=geo::ControlPoint::from_arg)]
+    gcp: Vec<geo::ControlPoint>,
+
+    #[clap(long)]
+    gcps: Option<String>,
+
+    #[clap(long, value_parser=geo::Model::from_arg, default_value_t)]
+    fit: geo::Model,
+
+    #[clap(long, conflicts_with_all=["stroke", "stroke_tolerance", "stroke_width", "fill", "class", "id", "parent", "index_stroke_width"])]
+    rules: Option<String>,
+
+    #[clap(long, value_parser=Color::from_arg)]
+    stroke: Option<Color>,
+
+    #[clap(long, default_value_t = 0.0)]
+    stroke_tolerance: f64,
+
+    #[clap(long, value_parser=Range::from_arg)]
+    stroke_width: Option<Range>,
+
+    #[clap(long)]
+    fill: Option<String>,
+
+    #[clap(long)]
+    class: Option<String>,
+
+    #[clap(long)]
+    id: Option<String>,
+
+    #[clap(long)]
+    parent: Option<String>,
+
+    #[clap(long, value_parser=Range::from_arg)]
+    index_stroke_width: Option<Range>,
+
+    #[clap(long, default_value_t = 25.0)]
+    label_distance: f64,
+
+    #[clap(long)]
+    contour_interval: Option<f64>,
+}
+
+impl Args {
+    fn matcher(&self) -> Result<Matcher, Box<dyn Error>> {
+        if let Some(rules) = &self.rules {
+            return Matcher::open(rules);
+        }
+
+        let rule = matcher::Rule {
+            stroke: self.stroke,
+            stroke_tolerance: self.stroke_tolerance,
+            stroke_width: self.stroke_width.clone(),
+            fill: self.fill.clone(),
+            class: self.class.clone(),
+            id: self.id.clone(),
+            parent: self.parent.clone(),
+            kind: None,
+        };
+
+        let matcher = if rule.stroke.is_none()
+            && rule.stroke_width.is_none()
+            && rule.fill.is_none()
+            && rule.class.is_none()
+            && rule.id.is_none()
+            && rule.parent.is_none()
+        {
+            Matcher::default()
+        } else {
+            Matcher::new(vec![rule])
+        };
+
+        // Index contours share the style of the others except for a heavier
+        // stroke, so derive their rules from the ones we already have.
+        match &self.index_stroke_width {
+            Some(width) => {
+                let (index, rest): (Vec<_>, Vec<_>) = matcher
+                    .rules()
+                    .iter()
+                    .map(|r| {
+                        (
+                            matcher::Rule {
+                                stroke_width: Some(width.clone()),
+                                kind: Some(Kind::Index),
+                                ..r.clone()
+                            },
+                            matcher::Rule {
+                                kind: Some(Kind::Intermediate),
+                                ..r.clone()
+                            },
+                        )
+                    })
+                    .unzip();
+                Ok(Matcher::new(index.into_iter().chain(rest).collect()))
+            }
+            None => Ok(matcher),
+        }
+    }
+
+    // Control points come from --gcp and from the JSON array of
+    // "x,y=location" strings in --gcps.
+    fn control_points(&self) -> Result<Vec<geo::ControlPoint>, Box<dyn Error>> {
+        let mut points = self.gcp.clone();
+        if let Some(path) = &self.gcps {
+            let r = BufReader::new(fs::File::open(path)?);
+            points.extend(serde_json::from_reader::<_, Vec<geo::ControlPoint>>(r)?);
+        }
+        Ok(points)
+    }
 }
 
 fn get_viewbox(root: RefNode) -> Result<Rect, Box<dyn Error>> {
@@ -23,37 +147,237 @@ fn get_viewbox(root: RefNode) -> Result<Rect, Box<dyn Error>> {
     viewbox.parse()
 }
 
-fn is_contour(path: RefNode) -> bool {
-    path.get_attribute("stroke") == Some(String::from("rgb(69.802856%, 69.802856%, 69.802856%)"))
-        && path.get_attribute("fill") == Some(String::from("none"))
-        && path.get_attribute("stroke-width") == Some(String::from("0.99001"))
+fn is_element(node: &RefNode) -> bool {
+    node.node_type() == NodeType::Element
 }
 
-pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
-    let r = fs::File::open(&args.src)?;
+fn get_style_property(node: &RefNode, name: &str) -> Option<String> {
+    node.get_attribute("style")?.split(';').find_map(|decl| {
+        let (k, v) = decl.split_once(':')?;
+        if k.trim() == name {
+            Some(v.trim().to_owned())
+        } else {
+            None
+        }
+    })
+}
+
+// Looks up a presentation property, inheriting from ancestors as SVG does.
+fn get_property(node: &RefNode, name: &str) -> Option<String> {
+    let mut node = Some(node.clone());
+    while let Some(n) = node.filter(is_element) {
+        if let Some(v) = get_style_property(&n, name).or_else(|| n.get_attribute(name)) {
+            return Some(v);
+        }
+        node = n.parent_node();
+    }
+    None
+}
+
+fn get_style(node: &RefNode) -> matcher::Style {
+    let mut groups = Vec::new();
+    let mut parent = node.parent_node();
+    while let Some(p) = parent.filter(is_element) {
+        if p.node_name().local_name() == "g" {
+            if let Some(id) = p.get_attribute("id") {
+                groups.push(id);
+            }
+        }
+        parent = p.parent_node();
+    }
+
+    matcher::Style {
+        stroke: get_property(node, "stroke").and_then(|s| s.parse().ok()),
+        stroke_width: get_property(node, "stroke-width")
+            .and_then(|s| s.trim_end_matches("px").trim().parse().ok()),
+        fill: get_property(node, "fill"),
+        id: node.get_attribute("id"),
+        classes: node
+            .get_attribute("class")
+            .map(|c| c.split_whitespace().map(String::from).collect())
+            .unwrap_or_default(),
+        groups,
+    }
+}
+
+// Composes the transforms of the node and all of its ancestors into a single
+// transform from the node's user space to the root's.
+fn get_transform(node: &RefNode) -> Result<Transform, Box<dyn Error>> {
+    let mut tx = Transform::identity();
+    let mut node = Some(node.clone());
+    while let Some(n) = node.filter(is_element) {
+        if let Some(t) = n.get_attribute("transform") {
+            tx = tx.then(&t.parse()?);
+        }
+        node = n.parent_node();
+    }
+    Ok(tx)
+}
+
+fn get_text(node: &RefNode) -> String {
+    node.child_nodes()
+        .iter()
+        .map(|n| match n.node_type() {
+            NodeType::Text | NodeType::CData => n.node_value().unwrap_or_default(),
+            NodeType::Element => get_text(n),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+// Finds the anchor of a text element, which may be on a child tspan. Only the
+// first value is used when x or y holds a list of glyph positions, and missing
+// coordinates default to zero as they do in SVG.
+fn get_text_position(node: &RefNode) -> Point {
+    fn find(node: &RefNode) -> Option<Point> {
+        let first = |name: &str| -> Option<f64> {
+            node.get_attribute(name)?
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .find(|v| !v.is_empty())?
+                .parse()
+                .ok()
+        };
+        match (first("x"), first("y")) {
+            (None, None) => node
+                .child_nodes()
+                .iter()
+                .filter(|n| is_element(n))
+                .find_map(find),
+            (x, y) => Some(Point::from_xy(x.unwrap_or(0.0), y.unwrap_or(0.0))),
+        }
+    }
+    find(node).unwrap_or(Point::from_xy(0.0, 0.0))
+}
+
+// What was read from a map, before it is made into contours.
+pub(crate) struct Source {
+    pub size: Size,
+    pub matches: Vec<Match>,
+    pub labels: Vec<Label>,
+}
+
+fn read_svg(src: &str, matcher: &Matcher) -> Result<Source, Box<dyn Error>> {
+    let r = fs::File::open(src)?;
     let r = BufReader::new(r);
     let doc = xml_dom::parser::read_reader(r)?;
     let root = doc.document_element().ok_or("no root element")?;
     let view_box = get_viewbox(root.clone())?;
+    let origin = Transform::translate(-view_box.x(), -view_box.y());
 
     let root = as_element(&root)?;
-    let mut paths = root
+    let matches = root
         .get_elements_by_tag_name("path")
         .iter()
-        .filter(|&n| is_contour(n.clone()))
-        .map(|n| n.get_attribute("d").ok_or("no d")?.parse::<Path>())
-        .collect::<Result<Vec<_>, _>>()?;
+        .filter_map(|n| {
+            let style = get_style(n);
+            matcher
+                .find(&style)
+                .map(|rule| (n, rule.kind, style.stroke_width))
+        })
+        .map(|(n, kind, stroke_width)| {
+            let mut path = n.get_attribute("d").ok_or("no d")?.parse::<Path>()?;
+            let tx = get_transform(n)?.then(&origin);
+            path.transform_into(|p| tx.apply(p));
+            Ok(Match {
+                path,
+                kind,
+                stroke_width,
+            })
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
 
-    for path in &mut paths {
-        path.transform(|p| Point::from_xy(p.x() - view_box.x(), p.y() - view_box.y()));
-    }
+    let labels = root
+        .get_elements_by_tag_name("text")
+        .iter()
+        .map(|n| {
+            let at = get_transform(n)?.then(&origin).apply(&get_text_position(n));
+            Ok(Label::parse(at, &get_text(n)))
+        })
+        .filter_map(|l| l.transpose())
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
 
-    let data = Data {
+    Ok(Source {
         size: Size::new(view_box.width(), view_box.height()),
-        region: args.region.clone(),
-        paths,
+        matches,
+        labels,
+    })
+}
+
+// Contours whose rule didn't say what kind they are get classified by stroke
+// width: anything notably heavier than the most common width is an index
+// contour.
+fn classify(matches: Vec<Match>) -> Vec<Contour> {
+    let key = |w: f64| (w * 1000.0).round() as i64;
+    let mut counts = std::collections::HashMap::new();
+    for w in matches
+        .iter()
+        .filter(|m| m.kind.is_none())
+        .filter_map(|m| m.stroke_width)
+    {
+        *counts.entry(key(w)).or_insert(0usize) += 1;
+    }
+    let modal = counts
+        .into_iter()
+        .max_by_key(|&(w, n)| (n, -w))
+        .map(|(w, _)| w as f64 / 1000.0);
+
+    matches
+        .into_iter()
+        .map(|m| {
+            let kind = match (m.kind, m.stroke_width, modal) {
+                (Some(kind), _, _) => kind,
+                (None, Some(w), Some(modal)) if w > modal * 1.5 => Kind::Index,
+                _ => Kind::Intermediate,
+            };
+            Contour {
+                path: m.path,
+                kind,
+                elevation: None,
+            }
+        })
+        .collect()
+}
+
+pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let matcher = args.matcher()?;
+
+    let Source {
+        size,
+        matches,
+        labels,
+    } = if pdf::is_pdf(&args.src) {
+        pdf::read_page(&args.src, args.page, &matcher)?
+    } else {
+        read_svg(&args.src, &matcher)?
+    };
+
+    let mut contours = classify(matches);
+    // Elevations are worked out from labels, which only come from real text.
+    // pdftocairo's SVG draws text as glyphs, so it has none.
+    if labels.is_empty() && args.contour_interval.is_some() {
+        eprintln!(
+            "warning: no elevation labels were found, so --contour-interval has nothing to count from"
+        );
+    }
+    elevation::assign_labels(&mut contours, &labels, args.label_distance);
+    if let Some(interval) = args
+        .contour_interval
+        .or_else(|| elevation::infer_interval(&contours))
+    {
+        elevation::infer_elevations(&mut contours, interval);
+    }
+
+    let points = args.control_points()?;
+    let fit = if points.is_empty() {
+        None
+    } else {
+        let fit = geo::Fit::new(args.fit, points, args.projection)?;
+        println!("{} fit, rms residual = {:.3}", fit.model, fit.rms);
+        Some(fit)
     };
 
+    let data = Data::new(size, args.region.clone(), args.projection, fit, contours);
+
     serde_json::to_writer(&mut fs::File::create(&args.dst)?, &data)?;
 
     Ok(())
@@ -63,5 +387,205 @@ pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
 pub struct Data {
     pub size: Size,
     pub region: geo::Rect,
-    pub paths: Vec<Path>,
+    // Extractions from before projections were recorded assumed the map was
+    // equirectangular.
+    #[serde(default)]
+    pub projection: geo::Projection,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fit: Option<geo::Fit>,
+    #[serde(alias = "paths")]
+    pub contours: Vec<Contour>,
+    #[serde(skip)]
+    index: OnceLock<Index>,
+    #[serde(skip)]
+    tints: OnceLock<Tints>,
+}
+
+impl Data {
+    pub fn new(
+        size: Size,
+        region: geo::Rect,
+        projection: geo::Projection,
+        fit: Option<geo::Fit>,
+        contours: Vec<Contour>,
+    ) -> Self {
+        Self {
+            size,
+            region,
+            projection,
+            fit,
+            contours,
+            index: OnceLock::new(),
+            tints: OnceLock::new(),
+        }
+    }
+
+    // Reads either extracted JSON or a packed file, telling them apart by the
+    // packed file's magic bytes.
+    // A cached index next to the file is used if it's still fresh.
+    pub fn open<P: AsRef<std::path::Path>>(src: P) -> Result<Self, Box<dyn Error>> {
+        let f = fs::File::open(&src)?;
+        let mem = unsafe { memmap::MmapOptions::new().map(&f)? };
+        let data: Data = if pack::is_packed(&mem) {
+            pack::read(&mem)?
+        } else {
+            serde_json::from_slice(&mem)?
+        };
+        if let Some(index) = Index::open(&src).filter(|i| i.len() == data.contours.len()) {
+            let _ = data.index.set(index);
+        }
+        Ok(data)
+    }
+
+    // The spatial index over the contours' bounds, built the first time it's
+    // needed.
+    pub fn index(&self) -> &Index {
+        self.index.get_or_init(|| {
+            Index::build(
+                &self
+                    .contours
+                    .iter()
+                    .map(|c| c.path.bounds())
+                    .collect::<Vec<_>>(),
+            )
+        })
+    }
+
+    // The contours closed into bands of elevation, worked out the first time
+    // they're needed.
+    pub fn tints(&self) -> &Tints {
+        self.tints.get_or_init(|| Tints::new(self))
+    }
+
+    // Control points, when there are any, georeference the map more precisely
+    // than the corners of the region.
+    pub fn georef(&self) -> Result<geo::Georef, Box<dyn Error>> {
+        match &self.fit {
+            Some(fit) => Ok(geo::Georef::from_fit(fit, self.projection)),
+            None => geo::Georef::from_region(&self.region, &self.size, self.projection),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Index,
+    #[default]
+    Intermediate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "ContourRepr")]
+pub struct Contour {
+    pub path: Path,
+    pub kind: Kind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elevation: Option<f64>,
+}
+
+// Older extractions stored each contour as a bare path string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ContourRepr {
+    Path(Path),
+    Contour {
+        path: Path,
+        #[serde(default)]
+        kind: Kind,
+        #[serde(default)]
+        elevation: Option<f64>,
+    },
+}
+
+impl From<ContourRepr> for Contour {
+    fn from(r: ContourRepr) -> Self {
+        match r {
+            ContourRepr::Path(path) => Contour {
+                path,
+                kind: Kind::default(),
+                elevation: None,
+            },
+            ContourRepr::Contour {
+                path,
+                kind,
+                elevation,
+            } => Contour {
+                path,
+                kind,
+                elevation,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[clap(flatten)]
+        args: Args,
+    }
+
+    #[test]
+    fn nested_groups_apply_innermost_first() {
+        let doc = xml_dom::parser::read_xml(
+            r#"<svg><g transform="translate(100 0)"><g transform="scale(2)">
+                <path id="p" transform="translate(1 1)" d="M0 0"/>
+            </g></g></svg>"#,
+        )
+        .unwrap();
+        let root = doc.document_element().unwrap();
+        let path = as_element(&root).unwrap().get_elements_by_tag_name("path");
+        let tx = get_transform(&path[0]).unwrap();
+        // The path's own translation, then the scale, then the outer group's
+        // translation.
+        assert_eq!(
+            tx.apply(&Point::from_xy(1.0, 0.0)),
+            Point::from_xy(104.0, 2.0)
+        );
+    }
+
+    #[test]
+    fn pdf_labels_give_elevations() {
+        let dst = std::env::temp_dir().join(format!("topo-labels-{}.json", std::process::id()));
+        let cli = Cli::parse_from([
+            "extract",
+            "35°38′47″N 080°03′00″W-35°29′20″N 079°51′00″W",
+            concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/labels.pdf"),
+            dst.to_str().unwrap(),
+            "--stroke",
+            "#996633",
+            "--contour-interval",
+            "20",
+        ]);
+        run(&cli.args).unwrap();
+        let data = Data::open(&dst).unwrap();
+        fs::remove_file(&dst).unwrap();
+
+        // The fixture's lines climb from 100 at the bottom of the page to 200
+        // at the top, with only the two index lines labeled.
+        let mut contours = data
+            .contours
+            .iter()
+            .map(|c| (c.path.bounds().y(), c.kind, c.elevation))
+            .collect::<Vec<_>>();
+        contours.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let found = contours.iter().map(|c| (c.1, c.2)).collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                (Kind::Index, Some(100.0)),
+                (Kind::Intermediate, Some(120.0)),
+                (Kind::Intermediate, Some(140.0)),
+                (Kind::Intermediate, Some(160.0)),
+                (Kind::Intermediate, Some(180.0)),
+                (Kind::Index, Some(200.0)),
+            ]
+        );
+    }
 }
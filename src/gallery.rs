@@ -0,0 +1,146 @@
+use std::{error::Error, fs, path::Path};
+
+use cairo::{Context, FontSlant, FontWeight, ImageSurface};
+
+use crate::{
+    geo,
+    render_many::{Entry, Manifest},
+    Size,
+};
+
+const MARGIN: f64 = 16.0;
+const FONT_SIZE: f64 = 12.0;
+const LINE_HEIGHT: f64 = 16.0;
+
+// The seed, theme and location of a render, one per line.
+fn label(entry: &Entry, notation: geo::Notation) -> [String; 3] {
+    [
+        entry.params.seed.to_string(),
+        match entry.params.theme.index() {
+            Some(idx) => format!("theme {}", idx),
+            None => entry.params.theme.to_string(),
+        },
+        entry.params.location.display(notation).to_string(),
+    ]
+}
+
+// Lays the renders in dir out as a grid of labeled thumbnails, thumb_width
+// wide, in the order of the manifest. The renders must be PNGs of size.
+pub fn contact_sheet(
+    dir: &Path,
+    manifest: &Manifest,
+    size: &Size,
+    notation: geo::Notation,
+    thumb_width: f64,
+    columns: usize,
+    dst: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let n = manifest.renders.len();
+    if n == 0 {
+        return Err("there are no renders for a contact sheet".into());
+    }
+    let columns = columns.clamp(1, n);
+    let rows = n.div_ceil(columns);
+    let thumb_height = (thumb_width * size.height() / size.width()).round();
+    let cell_w = thumb_width + MARGIN;
+    let cell_h = thumb_height + 3.0 * LINE_HEIGHT + MARGIN;
+
+    let surface = ImageSurface::create(
+        cairo::Format::ARgb32,
+        (MARGIN + columns as f64 * cell_w) as i32,
+        (MARGIN + rows as f64 * cell_h) as i32,
+    )?;
+    let ctx = Context::new(&surface)?;
+    ctx.set_source_rgb(1.0, 1.0, 1.0);
+    ctx.paint()?;
+    ctx.select_font_face("Helvetica Neue", FontSlant::Normal, FontWeight::Normal);
+    ctx.set_font_size(FONT_SIZE);
+
+    // Renders are read one at a time so a large batch doesn't have to fit in
+    // memory at full size.
+    for (i, entry) in manifest.renders.iter().enumerate() {
+        let x = MARGIN + (i % columns) as f64 * cell_w;
+        let y = MARGIN + (i / columns) as f64 * cell_h;
+
+        let img = ImageSurface::create_from_png(&mut fs::File::open(dir.join(&entry.file))?)?;
+        ctx.save()?;
+        ctx.rectangle(x, y, thumb_width, thumb_height);
+        ctx.clip();
+        ctx.translate(x, y);
+        ctx.scale(
+            thumb_width / img.width() as f64,
+            thumb_height / img.height() as f64,
+        );
+        ctx.set_source_surface(&img, 0.0, 0.0)?;
+        ctx.source().set_filter(cairo::Filter::Good);
+        ctx.paint()?;
+        ctx.restore()?;
+
+        ctx.set_source_rgb(0.2, 0.2, 0.2);
+        for (j, line) in label(entry, notation).iter().enumerate() {
+            ctx.move_to(x, y + thumb_height + (j + 1) as f64 * LINE_HEIGHT);
+            ctx.show_text(line)?;
+        }
+    }
+
+    surface.write_to_png(&mut fs::File::create(dst)?)?;
+    Ok(())
+}
+
+// A static page, written next to the renders, that shows each one with its
+// labels. The seed, given to render with the batch's other options, renders
+// it again at full size.
+pub fn html(
+    manifest: &Manifest,
+    notation: geo::Notation,
+    thumb_width: f64,
+    dst: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut page = String::new();
+    page.push_str(&format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{}</title>
+<style>
+body {{ font: 12px "Helvetica Neue", sans-serif; color: #333; margin: 16px; }}
+main {{ display: grid; grid-template-columns: repeat(auto-fill, {}px); gap: 16px; }}
+img {{ width: 100%; display: block; }}
+figure {{ margin: 0; }}
+code {{ user-select: all; }}
+</style>
+</head>
+<body>
+<main>
+"#,
+        escape(&manifest.seed.to_string()),
+        thumb_width
+    ));
+    for entry in &manifest.renders {
+        let file = escape(&entry.file);
+        let [seed, theme, location] = label(entry, notation).map(|l| escape(&l));
+        let preview = if entry.file.ends_with(".pdf") {
+            format!(r#"<a href="{}">{}</a>"#, file, file)
+        } else {
+            format!(
+                r#"<a href="{}"><img src="{}" loading="lazy"></a>"#,
+                file, file
+            )
+        };
+        page.push_str(&format!(
+            "<figure>\n{}\n<figcaption><code>{}</code><br>{}<br>{}</figcaption>\n</figure>\n",
+            preview, seed, theme, location
+        ));
+    }
+    page.push_str("</main>\n</body>\n</html>\n");
+    fs::write(dst, page)?;
+    Ok(())
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,7 +1,72 @@
 use std::{error::Error, fmt, str::FromStr};
 
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::{de, ser, Deserialize, Serialize};
 
+use crate::{Point, Size, Transform};
+
+mod fit;
+mod projection;
+pub use fit::{ControlPoint, Fit, Model, Polynomial};
+pub use projection::Projection;
+
+lazy_static! {
+    // A single coordinate in decimal degrees, degrees and decimal minutes, or
+    // degrees, minutes and (possibly fractional) seconds, with an optional
+    // sign or hemisphere.
+    static ref LATLNG_PATTERN: Regex = {
+        let coord = r#"([+-])?\s*(\d+(?:\.\d+)?)\s*°?(?:\s*(\d+(?:\.\d+)?)\s*[′'])?(?:\s*(\d+(?:\.\d+)?)\s*[″"])?\s*([NSEWnsew])?"#;
+        Regex::new(&format!(r"^\s*{}(?:\s*,\s*|\s+){}\s*$", coord, coord)).unwrap()
+    };
+    static ref TRAILING_ZEROS: Regex = Regex::new(r"\.?0+″").unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Notation {
+    #[default]
+    Dms,
+    Ddm,
+    Decimal,
+}
+
+impl Notation {
+    pub fn from_arg(s: &str) -> Result<Self, String> {
+        s.parse().map_err(|_| format!("invalid notation: {}", s))
+    }
+
+    fn default_precision(&self) -> usize {
+        match self {
+            Notation::Dms => 0,
+            Notation::Ddm => 3,
+            Notation::Decimal => 6,
+        }
+    }
+}
+
+impl FromStr for Notation {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dms" => Ok(Notation::Dms),
+            "ddm" => Ok(Notation::Ddm),
+            "decimal" | "dd" => Ok(Notation::Decimal),
+            _ => Err(format!("unknown notation: {}", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for Notation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Notation::Dms => write!(f, "dms"),
+            Notation::Ddm => write!(f, "ddm"),
+            Notation::Decimal => write!(f, "decimal"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LatLng {
     pub lat: f64,
@@ -13,26 +78,143 @@ impl LatLng {
         LatLng { lat, lng }
     }
 
+    pub fn from_arg(s: &str) -> Result<Self, String> {
+        s.parse().map_err(|_| format!("invalid location: {}", s))
+    }
+
     pub fn to_dms(&self) -> String {
-        let (lat_d, lat_m, lat_s) = to_dms(self.lat);
-        let (lng_d, lng_m, lng_s) = to_dms(self.lng);
-        format!(
-            "{:02}°{:02}′{:02}″{} {:03}°{:02}′{:02}″{}",
-            lat_d,
-            lat_m,
-            lat_s,
-            if self.lat < 0.0 { 'S' } else { 'N' },
-            lng_d,
-            lng_m,
-            lng_s,
-            if self.lng < 0.0 { 'W' } else { 'E' }
-        )
+        self.format(Notation::Dms, 0)
+    }
+
+    // Formats the location in the given notation, with precision decimal
+    // places on the smallest unit (seconds, minutes or degrees).
+    pub fn format(&self, notation: Notation, precision: usize) -> String {
+        let hemi = |v: f64, pos: char, neg: char| if v < 0.0 { neg } else { pos };
+        match notation {
+            Notation::Decimal => format!("{:.p$}, {:.p$}", self.lat, self.lng, p = precision),
+            Notation::Ddm => {
+                let (lat_d, lat_m) = to_ddm(self.lat, precision);
+                let (lng_d, lng_m) = to_ddm(self.lng, precision);
+                let w = width(precision);
+                format!(
+                    "{:02}°{:0w$.p$}′{} {:03}°{:0w$.p$}′{}",
+                    lat_d,
+                    lat_m,
+                    hemi(self.lat, 'N', 'S'),
+                    lng_d,
+                    lng_m,
+                    hemi(self.lng, 'E', 'W'),
+                    w = w,
+                    p = precision
+                )
+            }
+            Notation::Dms => {
+                let (lat_d, lat_m, lat_s) = to_dms(self.lat, precision);
+                let (lng_d, lng_m, lng_s) = to_dms(self.lng, precision);
+                let w = width(precision);
+                format!(
+                    "{:02}°{:02}′{:0w$.p$}″{} {:03}°{:02}′{:0w$.p$}″{}",
+                    lat_d,
+                    lat_m,
+                    lat_s,
+                    hemi(self.lat, 'N', 'S'),
+                    lng_d,
+                    lng_m,
+                    lng_s,
+                    hemi(self.lng, 'E', 'W'),
+                    w = w,
+                    p = precision
+                )
+            }
+        }
+    }
+
+    pub fn display(&self, notation: Notation) -> Formatted<'_> {
+        Formatted {
+            location: self,
+            notation,
+        }
+    }
+}
+
+// Displays a location in a chosen notation, honoring the formatter's
+// precision when one is given.
+pub struct Formatted<'a> {
+    location: &'a LatLng,
+    notation: Notation,
+}
+
+impl fmt::Display for Formatted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(self.notation.default_precision());
+        write!(f, "{}", self.location.format(self.notation, precision))
     }
 }
 
 impl fmt::Display for LatLng {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_dms())
+        write!(f, "{}", self.display(Notation::Dms))
+    }
+}
+
+fn width(precision: usize) -> usize {
+    if precision == 0 {
+        2
+    } else {
+        precision + 3
+    }
+}
+
+fn to_ddm(v: f64, precision: usize) -> (u64, f64) {
+    let scale = 10f64.powi(precision as i32);
+    let units = (v.abs() * 60.0 * scale).round() as u64;
+    let per_degree = (60.0 * scale) as u64;
+    (units / per_degree, (units % per_degree) as f64 / scale)
+}
+
+fn to_dms(v: f64, precision: usize) -> (u64, u64, f64) {
+    let scale = 10f64.powi(precision as i32);
+    let units = (v.abs() * 3600.0 * scale).round() as u64;
+    let per_minute = (60.0 * scale) as u64;
+    let per_degree = 60 * per_minute;
+    (
+        units / per_degree,
+        (units % per_degree) / per_minute,
+        (units % per_minute) as f64 / scale,
+    )
+}
+
+fn parse_coord(
+    caps: &regex::Captures,
+    offset: usize,
+) -> Result<(f64, Option<char>), Box<dyn Error>> {
+    let group = |i: usize| caps.get(offset + i).map(|m| m.as_str());
+    let deg = group(1).ok_or("no degrees")?;
+    let (min, sec) = (group(2), group(3));
+
+    // Only the last component may have a fractional part.
+    if (min.is_some() || sec.is_some()) && deg.contains('.') {
+        return Err(format!("fractional degrees with minutes: {}", deg).into());
+    }
+    if sec.is_some() && min.is_none_or(|m| m.contains('.')) {
+        return Err("seconds require whole minutes".into());
+    }
+
+    let min = min.map_or(Ok(0.0), |m| m.parse::<f64>())?;
+    let sec = sec.map_or(Ok(0.0), |s| s.parse::<f64>())?;
+    if min >= 60.0 || sec >= 60.0 {
+        return Err("minutes and seconds must be less than 60".into());
+    }
+
+    let v = deg.parse::<f64>()? + min / 60.0 + sec / 3600.0;
+    let hemi = group(4)
+        .and_then(|h| h.chars().next())
+        .map(|c| c.to_ascii_uppercase());
+    match (group(0), hemi) {
+        (Some("-"), Some(_)) => Err("both a sign and a hemisphere were given".into()),
+        (Some("-"), None) => Ok((-v, None)),
+        (_, Some(h @ ('S' | 'W'))) => Ok((-v, Some(h))),
+        (_, h) => Ok((v, h)),
     }
 }
 
@@ -40,46 +222,45 @@ impl FromStr for LatLng {
     type Err = Box<dyn Error>;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let re = regex::Regex::new(
-            r#"(\d+)°(\d+)[′'](\d+)[″"]([NSns]) (\d+)°(\d+)[′'](\d+)[″"]([EWew])"#,
-        )
-        .unwrap();
-
-        let caps = re.captures(s).ok_or(format!("invalid location: {}", s))?;
-
-        let lat_d = caps.get(1).unwrap().as_str().parse::<i32>()?;
-        let lat_m = caps.get(2).unwrap().as_str().parse::<i32>()?;
-        let lat_s = caps.get(3).unwrap().as_str().parse::<i32>()?;
-        let lat_v = lat_d as f64 + (lat_m as f64) / 60.0 + (lat_s as f64) / 3600.0;
-        let lat_v = match caps.get(4).unwrap().as_str() {
-            "N" | "n" => Ok(lat_v),
-            "S" | "s" => Ok(-lat_v),
-            _ => Err(format!("invalid location: {}", s)),
-        }?;
-
-        let lng_d = caps.get(5).unwrap().as_str().parse::<i32>()?;
-        let lng_m = caps.get(6).unwrap().as_str().parse::<i32>()?;
-        let lng_s = caps.get(7).unwrap().as_str().parse::<i32>()?;
-        let lng_v = lng_d as f64 + (lng_m as f64) / 60.0 + (lng_s as f64) / 3600.0;
-        let lng_v = match caps.get(8).unwrap().as_str() {
-            "E" | "e" => Ok(lng_v),
-            "W" | "w" => Ok(-lng_v),
-            _ => Err(format!("invalid location: {}", s)),
-        }?;
-
-        Ok(Self {
-            lat: lat_v,
-            lng: lng_v,
-        })
+        let invalid = || format!("invalid location: {}", s);
+        let caps = LATLNG_PATTERN.captures(s).ok_or_else(invalid)?;
+        let (a, a_hemi) = parse_coord(&caps, 1)?;
+        let (b, b_hemi) = parse_coord(&caps, 6)?;
+
+        // Latitude normally comes first, but the hemispheres can say otherwise.
+        let (lat, lng) = match (a_hemi, b_hemi) {
+            (Some('E' | 'W'), Some('N' | 'S') | None) | (None, Some('N' | 'S')) => (b, a),
+            (Some('E' | 'W'), Some('E' | 'W')) | (Some('N' | 'S'), Some('N' | 'S')) => {
+                return Err(invalid().into())
+            }
+            _ => (a, b),
+        };
+
+        if lat.abs() > 90.0 || lng.abs() > 180.0 {
+            return Err(format!("location out of range: {}", s).into());
+        }
+
+        Ok(Self { lat, lng })
     }
 }
 
 impl ser::Serialize for LatLng {
+    // Written as DMS, keeping fractional seconds only when there are any.
     fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
     where
         S: ser::Serializer,
     {
-        let v = format!("{}", self);
+        let whole = self.format(Notation::Dms, 0);
+        let v = if whole
+            .parse::<LatLng>()
+            .is_ok_and(|ll| (ll.lat - self.lat).abs() < 1e-9 && (ll.lng - self.lng).abs() < 1e-9)
+        {
+            whole
+        } else {
+            TRAILING_ZEROS
+                .replace_all(&self.format(Notation::Dms, 4), "″")
+                .into_owned()
+        };
         s.serialize_str(&v)
     }
 }
@@ -110,32 +291,6 @@ impl<'d> de::Visitor<'d> for LatLngVisitor {
     }
 }
 
-fn to_dms(v: f64) -> (i32, i32, i32) {
-    let v = v.abs();
-
-    let mut d = v as i32;
-
-    let v = v - d as f64;
-
-    let mut m = (v * 60.0) as i32;
-
-    let v = v - m as f64 / 60.0;
-
-    let mut s = (v * 3600.0).round() as i32;
-
-    if s == 60 {
-        s = 0;
-        m += 1;
-    }
-
-    if m == 60 {
-        m = 0;
-        d += 1;
-    }
-
-    (d, m, s)
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Rect {
     pub nw: LatLng,
@@ -156,14 +311,15 @@ impl FromStr for Rect {
     type Err = Box<dyn Error>;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (nw, se) = s
-            .split_once('-')
-            .ok_or(format!("invalid geo rect: {}", s))?;
-
-        let nw = nw.parse::<LatLng>()?;
-        let se = se.parse::<LatLng>()?;
-
-        Ok(Self { nw, se })
+        // Signed coordinates also contain dashes, so take the first split
+        // where both halves are locations.
+        s.match_indices('-')
+            .find_map(|(ix, _)| {
+                let nw = s[..ix].parse::<LatLng>().ok()?;
+                let se = s[ix + 1..].parse::<LatLng>().ok()?;
+                Some(Self { nw, se })
+            })
+            .ok_or_else(|| format!("invalid geo rect: {}", s).into())
     }
 }
 
@@ -172,3 +328,271 @@ impl fmt::Display for Rect {
         write!(f, "{}-{}", self.nw, self.se)
     }
 }
+
+/// A mapping between points on an extracted map, whose y axis grows downward,
+/// and geographic coordinates. Map points are related to the map's projected
+/// coordinates by an affine transform, or a polynomial one when fitted to
+/// control points, and the projection takes it from there.
+///
+/// ```
+/// use topo::{geo::{Georef, LatLng, Projection, Rect}, Point, Size};
+///
+/// let region: Rect = "35°38′47″N 080°03′00″W-35°29′20″N 079°51′00″W".parse().unwrap();
+/// let size = Size::new(2000.0, 3000.0);
+///
+/// for projection in ["equirectangular", "web-mercator", "utm:17n"] {
+///     let projection: Projection = projection.parse().unwrap();
+///     let georef = Georef::from_region(&region, &size, projection).unwrap();
+///
+///     let nw = georef.to_point(&region.nw);
+///     assert!(nw.distance_to(&Point::from_xy(0.0, 0.0)) < 1e-6);
+///     let se = georef.to_point(&region.se);
+///     assert!(se.distance_to(&Point::from_xy(2000.0, 3000.0)) < 1e-6);
+///
+///     let summit: LatLng = "35°33′00″N 079°58′00″W".parse().unwrap();
+///     let back = georef.to_latlng(&georef.to_point(&summit));
+///     assert!((back.lat - summit.lat).abs() < 1e-7 && (back.lng - summit.lng).abs() < 1e-7);
+///     assert_eq!(back.to_dms(), "35°33′00″N 079°58′00″W");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Georef {
+    to_world: Polynomial,
+    to_map: Polynomial,
+    projection: Projection,
+}
+
+impl Georef {
+    pub fn new(to_world: Transform, projection: Projection) -> Result<Self, Box<dyn Error>> {
+        let to_map = to_world.invert().ok_or("degenerate georeference")?;
+        Ok(Self {
+            to_world: Polynomial::from(&to_world),
+            to_map: Polynomial::from(&to_map),
+            projection,
+        })
+    }
+
+    pub fn from_fit(fit: &Fit, projection: Projection) -> Self {
+        Self {
+            to_world: fit.to_world.clone(),
+            to_map: fit.to_map.clone(),
+            projection,
+        }
+    }
+
+    pub fn from_region(
+        region: &Rect,
+        size: &Size,
+        projection: Projection,
+    ) -> Result<Self, Box<dyn Error>> {
+        let nw = projection.project(&region.nw);
+        let se = projection.project(&region.se);
+        let to_world = Transform::scale(
+            (se.x() - nw.x()) / size.width(),
+            (se.y() - nw.y()) / size.height(),
+        )
+        .then(&Transform::translate(nw.x(), nw.y()));
+        let to_map = to_world
+            .invert()
+            .ok_or_else(|| format!("the region {} has no area", region))?;
+        Ok(Self {
+            to_world: Polynomial::from(&to_world),
+            to_map: Polynomial::from(&to_map),
+            projection,
+        })
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn to_latlng(&self, p: &Point) -> LatLng {
+        self.projection.unproject(&self.to_world.apply(p))
+    }
+
+    pub fn to_point(&self, location: &LatLng) -> Point {
+        self.to_map.apply(&self.projection.project(location))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ll(s: &str) -> LatLng {
+        s.parse().unwrap()
+    }
+
+    fn assert_near(a: &Point, b: &Point) {
+        assert!(a.distance_to(b) < 1e-6, "{} != {}", a, b);
+    }
+
+    fn assert_same_place(a: &LatLng, b: &LatLng) {
+        assert!(
+            (a.lat - b.lat).abs() < 1e-7 && (a.lng - b.lng).abs() < 1e-7,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn parses_locations() {
+        let cases = [
+            // Decimal degrees, signed or with hemispheres.
+            ("35.5, -79.25", 35.5, -79.25),
+            ("35.5 -79.25", 35.5, -79.25),
+            ("-35.5,+79.25", -35.5, 79.25),
+            ("35.5°S 79.25°E", -35.5, 79.25),
+            ("35.5n, 79.25w", 35.5, -79.25),
+            // Degrees and decimal minutes.
+            ("35°30.5′N 079°15.3′W", 35.0 + 30.5 / 60.0, -79.255),
+            ("-35 30.5', 79 15.3'", -35.0 - 30.5 / 60.0, 79.255),
+            // Degrees, minutes and seconds.
+            (
+                "35°38′47″N 080°03′00″W",
+                35.0 + 38.0 / 60.0 + 47.0 / 3600.0,
+                -80.05,
+            ),
+            (
+                "35 38' 47.5\" S, 80 3' 0\" E",
+                -35.0 - 38.0 / 60.0 - 47.5 / 3600.0,
+                80.05,
+            ),
+            // Hemispheres can put the longitude first.
+            ("79.25W 35.5N", 35.5, -79.25),
+            ("080°03′00″W, 35°38′00″S", -35.0 - 38.0 / 60.0, -80.05),
+            ("90, 180", 90.0, 180.0),
+        ];
+        for (s, lat, lng) in cases {
+            let location = s
+                .parse::<LatLng>()
+                .unwrap_or_else(|e| panic!("{}: {}", s, e));
+            assert_same_place(&location, &LatLng::new(lat, lng));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_locations() {
+        let cases = [
+            "",
+            "35.5",
+            "35.5, -79.25, 10",
+            "north, west",
+            "91, 0",
+            "0, -180.5",
+            "-35.5S, 79.25E",
+            "35.5N 36.5S",
+            "79W 80E",
+            "35.5°30′N 79°W",
+            "35°30.5′15″N 79°W",
+            "35°15″N 79°W",
+            "35°60′N 79°W",
+            "35°30′60″N 79°W",
+        ];
+        for s in cases {
+            assert!(s.parse::<LatLng>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn parses_regions() {
+        let cases = [
+            ("36, -80 - 35, -79", (36.0, -80.0), (35.0, -79.0)),
+            ("36,-80-35,-79", (36.0, -80.0), (35.0, -79.0)),
+            // Every dash here could be a separator or a sign.
+            ("-35.1,-80.2--35.0,-80.0", (-35.1, -80.2), (-35.0, -80.0)),
+            ("-35.1 -80.2 - -35.0 -80.0", (-35.1, -80.2), (-35.0, -80.0)),
+            (
+                "35°38′47″N 080°03′00″W-35°29′20″N 079°51′00″W",
+                (35.0 + 38.0 / 60.0 + 47.0 / 3600.0, -80.05),
+                (35.0 + 29.0 / 60.0 + 20.0 / 3600.0, -79.85),
+            ),
+        ];
+        for (s, nw, se) in cases {
+            let rect = s.parse::<Rect>().unwrap_or_else(|e| panic!("{}: {}", s, e));
+            assert_same_place(&rect.nw, &LatLng::new(nw.0, nw.1));
+            assert_same_place(&rect.se, &LatLng::new(se.0, se.1));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_regions() {
+        let cases = [
+            "",
+            "36, -80",
+            "36, -80 -",
+            "- 35, -79",
+            "36, -80 35, -79",
+            "36, -80 - 35",
+            "36, -80 - 95, -79",
+        ];
+        for s in cases {
+            assert!(s.parse::<Rect>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn regions_round_trip_through_display() {
+        let s = "35°38′47″N 080°03′00″W-35°29′20″S 079°51′00″E";
+        assert_eq!(s.parse::<Rect>().unwrap().to_string(), s);
+    }
+
+    #[test]
+    fn maps_a_region_onto_the_map() {
+        let region = "36, -80 - 35, -79".parse::<Rect>().unwrap();
+        let georef = Georef::from_region(
+            &region,
+            &Size::new(100.0, 200.0),
+            Projection::Equirectangular,
+        )
+        .unwrap();
+        // The map's y axis grows southward.
+        for (location, p) in [
+            ("36, -80", (0.0, 0.0)),
+            ("35, -79", (100.0, 200.0)),
+            ("35.5, -79.5", (50.0, 100.0)),
+            ("35.75, -79.9", (10.0, 50.0)),
+        ] {
+            let location = ll(location);
+            let p = Point::from_xy(p.0, p.1);
+            assert_near(&georef.to_point(&location), &p);
+            assert_same_place(&georef.to_latlng(&p), &location);
+        }
+    }
+
+    #[test]
+    fn maps_projected_utm_coordinates() {
+        // Each map unit is a kilometer of UTM zone 17N, with the map's origin
+        // 1000 km north of the equator on the zone's central meridian.
+        let to_world =
+            Transform::scale(1000.0, -1000.0).then(&Transform::translate(500_000.0, 1_000_000.0));
+        let georef = Georef::new(to_world, "utm:17n".parse().unwrap()).unwrap();
+
+        let equator = georef.to_latlng(&Point::from_xy(0.0, 1000.0));
+        assert_same_place(&equator, &LatLng::new(0.0, -81.0));
+        assert_near(&georef.to_point(&equator), &Point::from_xy(0.0, 1000.0));
+
+        let origin = georef.to_latlng(&Point::from_xy(0.0, 0.0));
+        assert!((origin.lng + 81.0).abs() < 1e-9);
+        assert!((9.0..9.1).contains(&origin.lat), "{:?}", origin);
+
+        let summit = ll("35°33′00″N 079°58′00″W");
+        let p = georef.to_point(&summit);
+        assert!(p.x() > 0.0 && p.y() < 0.0, "{}", p);
+        assert_same_place(&georef.to_latlng(&p), &summit);
+    }
+
+    #[test]
+    fn regions_without_area_are_rejected() {
+        let size = Size::new(100.0, 100.0);
+        for region in ["35, -80 - 35, -79", "36, -80 - 35, -80"] {
+            let region = region.parse::<Rect>().unwrap();
+            let err = Georef::from_region(&region, &size, Projection::Equirectangular)
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains("has no area"), "{}", err);
+        }
+        assert!(Georef::new(Transform::scale(1.0, 0.0), Projection::Equirectangular).is_err());
+    }
+}
@@ -0,0 +1,386 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use serde::{de, ser, Deserialize, Serialize};
+
+use super::{LatLng, Projection};
+use crate::{Point, Transform};
+
+/// A point on the extracted map paired with the location it depicts.
+#[derive(Debug, Clone)]
+pub struct ControlPoint {
+    pub point: Point,
+    pub location: LatLng,
+}
+
+impl ControlPoint {
+    pub fn from_arg(s: &str) -> Result<Self, String> {
+        s.parse()
+            .map_err(|e| format!("invalid control point: {}: {}", s, e))
+    }
+}
+
+impl FromStr for ControlPoint {
+    type Err = Box<dyn Error>;
+
+    // Parses "x,y=location", e.g. "120.5,88=35°33′00″N 079°58′00″W".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (point, location) = s.split_once('=').ok_or("expected x,y=location")?;
+        let (x, y) = point.split_once(',').ok_or("expected x,y")?;
+        Ok(Self {
+            point: Point::from_xy(x.trim().parse()?, y.trim().parse()?),
+            location: location.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for ControlPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = self.location.display(super::Notation::Decimal);
+        match f.precision() {
+            Some(p) => write!(f, "{},{}={:.p$}", self.point.x(), self.point.y(), location),
+            None => write!(f, "{},{}={}", self.point.x(), self.point.y(), location),
+        }
+    }
+}
+
+impl ser::Serialize for ControlPoint {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        s.serialize_str(&format!("{:.9}", self))
+    }
+}
+
+impl<'d> de::Deserialize<'d> for ControlPoint {
+    fn deserialize<D>(de: D) -> Result<ControlPoint, D::Error>
+    where
+        D: de::Deserializer<'d>,
+    {
+        de.deserialize_str(ControlPointVisitor)
+    }
+}
+
+struct ControlPointVisitor;
+
+impl<'d> de::Visitor<'d> for ControlPointVisitor {
+    type Value = ControlPoint;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a string")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        ControlPoint::from_str(v).map_err(|e| E::custom(e.to_string()))
+    }
+}
+
+/// The kind of transform fitted to control points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Model {
+    #[default]
+    Affine,
+    Polynomial,
+}
+
+impl Model {
+    pub fn from_arg(s: &str) -> Result<Self, String> {
+        s.parse().map_err(|_| format!("invalid model: {}", s))
+    }
+
+    fn terms(&self) -> usize {
+        match self {
+            Model::Affine => 3,
+            Model::Polynomial => 6,
+        }
+    }
+}
+
+impl FromStr for Model {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "affine" => Ok(Model::Affine),
+            "polynomial" => Ok(Model::Polynomial),
+            _ => Err(format!("unknown model: {}", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Model::Affine => write!(f, "affine"),
+            Model::Polynomial => write!(f, "polynomial"),
+        }
+    }
+}
+
+/// A first or second order polynomial mapping between planes. Inputs are
+/// shifted and scaled before evaluation to keep the fit well conditioned when
+/// coordinates are large, as projected ones are.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Polynomial {
+    offset: [f64; 2],
+    scale: f64,
+    x: Vec<f64>,
+    y: Vec<f64>,
+}
+
+impl Polynomial {
+    // Least squares fit of a polynomial taking each of from to the
+    // corresponding point in to.
+    pub fn fit(model: Model, from: &[Point], to: &[Point]) -> Result<Self, Box<dyn Error>> {
+        let n = from.len() as f64;
+        let offset = [
+            from.iter().map(|p| p.x()).sum::<f64>() / n,
+            from.iter().map(|p| p.y()).sum::<f64>() / n,
+        ];
+        let scale = from
+            .iter()
+            .map(|p| (p.x() - offset[0]).abs().max((p.y() - offset[1]).abs()))
+            .fold(0.0, f64::max);
+        if scale == 0.0 {
+            return Err("control points are all in the same place".into());
+        }
+
+        let mut p = Self {
+            offset,
+            scale,
+            x: Vec::new(),
+            y: Vec::new(),
+        };
+        let rows = from
+            .iter()
+            .map(|pt| p.terms(pt)[..model.terms()].to_vec())
+            .collect::<Vec<_>>();
+        p.x = least_squares(&rows, &to.iter().map(|p| p.x()).collect::<Vec<_>>())?;
+        p.y = least_squares(&rows, &to.iter().map(|p| p.y()).collect::<Vec<_>>())?;
+        Ok(p)
+    }
+
+    fn terms(&self, p: &Point) -> [f64; 6] {
+        let u = (p.x() - self.offset[0]) / self.scale;
+        let v = (p.y() - self.offset[1]) / self.scale;
+        [1.0, u, v, u * u, u * v, v * v]
+    }
+
+    pub fn apply(&self, p: &Point) -> Point {
+        let terms = self.terms(p);
+        let eval = |c: &[f64]| c.iter().zip(terms).map(|(c, t)| c * t).sum();
+        Point::from_xy(eval(&self.x), eval(&self.y))
+    }
+}
+
+impl From<&Transform> for Polynomial {
+    fn from(t: &Transform) -> Self {
+        let [a, b, c, d, e, f] = t.coefficients();
+        Self {
+            offset: [0.0, 0.0],
+            scale: 1.0,
+            x: vec![e, a, c],
+            y: vec![f, b, d],
+        }
+    }
+}
+
+// Solves the normal equations for the coefficients that best combine the
+// columns of rows to give values.
+fn least_squares(rows: &[Vec<f64>], values: &[f64]) -> Result<Vec<f64>, Box<dyn Error>> {
+    let n = rows[0].len();
+    let mut m = vec![vec![0.0; n + 1]; n];
+    for (row, v) in rows.iter().zip(values) {
+        for (mi, ri) in m.iter_mut().zip(row) {
+            for (mij, rj) in mi.iter_mut().zip(row) {
+                *mij += ri * rj;
+            }
+            mi[n] += ri * v;
+        }
+    }
+
+    // Gaussian elimination with partial pivoting.
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))
+            .unwrap();
+        if m[pivot][col].abs() < 1e-12 {
+            return Err("control points are degenerate (are they in a line?)".into());
+        }
+        m.swap(col, pivot);
+        let pivot = m[col].clone();
+        for (r, row) in m.iter_mut().enumerate() {
+            if r != col {
+                let k = row[col] / pivot[col];
+                for (v, p) in row.iter_mut().zip(&pivot).skip(col) {
+                    *v -= k * p;
+                }
+            }
+        }
+    }
+    Ok(m.iter()
+        .enumerate()
+        .map(|(i, row)| row[n] / row[i])
+        .collect())
+}
+
+/// A georeference fitted to ground control points, kept along with the
+/// points themselves and the RMS of the residuals in map units.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fit {
+    pub model: Model,
+    pub points: Vec<ControlPoint>,
+    pub rms: f64,
+    pub(super) to_world: Polynomial,
+    pub(super) to_map: Polynomial,
+}
+
+impl Fit {
+    pub fn new(
+        model: Model,
+        points: Vec<ControlPoint>,
+        projection: Projection,
+    ) -> Result<Self, Box<dyn Error>> {
+        if points.len() < model.terms() {
+            return Err(format!(
+                "{} fits need at least {} control points, got {}",
+                model,
+                model.terms(),
+                points.len()
+            )
+            .into());
+        }
+
+        let map = points.iter().map(|p| p.point).collect::<Vec<_>>();
+        let world = points
+            .iter()
+            .map(|p| projection.project(&p.location))
+            .collect::<Vec<_>>();
+        let to_world = Polynomial::fit(model, &map, &world)?;
+        let to_map = Polynomial::fit(model, &world, &map)?;
+
+        let rms = (map
+            .iter()
+            .zip(&world)
+            .map(|(m, w)| to_map.apply(w).distance_to(m).powi(2))
+            .sum::<f64>()
+            / map.len() as f64)
+            .sqrt();
+
+        Ok(Self {
+            model,
+            points,
+            rms,
+            to_world,
+            to_map,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Control points whose locations are the map points taken through
+    // to_world and unprojected.
+    fn control_points(
+        to_world: &Transform,
+        projection: Projection,
+        map: &[(f64, f64)],
+    ) -> Vec<ControlPoint> {
+        map.iter()
+            .map(|&(x, y)| {
+                let point = Point::from_xy(x, y);
+                ControlPoint {
+                    point,
+                    location: projection.unproject(&to_world.apply(&point)),
+                }
+            })
+            .collect()
+    }
+
+    const MAP: [(f64, f64); 5] = [
+        (0.0, 0.0),
+        (2000.0, 0.0),
+        (0.0, 3000.0),
+        (2000.0, 3000.0),
+        (700.0, 1200.0),
+    ];
+
+    #[test]
+    fn recovers_an_exact_affine_transform() {
+        // Tolerances are in map units. UTM's series only round trip to a
+        // fraction of a millimeter, which is all its control points can pin
+        // the fit to.
+        let cases = [
+            (
+                Transform::new(1e-4, 2e-6, -3e-6, -1.2e-4, -80.05, 35.65),
+                Projection::Equirectangular,
+                1e-6,
+            ),
+            (
+                Transform::new(5.0, 0.2, 0.3, -5.0, 590_000.0, 3_950_000.0),
+                "utm:17n".parse().unwrap(),
+                1e-4,
+            ),
+        ];
+        for (to_world, projection, tolerance) in cases {
+            let points = control_points(&to_world, projection, &MAP);
+            let fit = Fit::new(Model::Affine, points, projection).unwrap();
+            assert!(fit.rms < 1e-6, "{}", fit.rms);
+
+            let p = Point::from_xy(1500.0, 250.0);
+            let world = to_world.apply(&p);
+            let unit = to_world
+                .apply(&Point::from_xy(1.0, 0.0))
+                .distance_to(&to_world.apply(&Point::from_xy(0.0, 0.0)));
+            assert!(fit.to_world.apply(&p).distance_to(&world) < tolerance * unit);
+            assert!(fit.to_map.apply(&world).distance_to(&p) < tolerance);
+        }
+    }
+
+    #[test]
+    fn reports_the_residuals_of_inexact_points() {
+        let to_world = Transform::new(1e-4, 0.0, 0.0, -1e-4, -80.0, 35.0);
+        let mut points = control_points(&to_world, Projection::Equirectangular, &MAP);
+        points[4].point = Point::from_xy(710.0, 1200.0);
+        let fit = Fit::new(Model::Affine, points, Projection::Equirectangular).unwrap();
+        assert!(fit.rms > 1.0 && fit.rms < 10.0, "{}", fit.rms);
+    }
+
+    #[test]
+    fn needs_enough_control_points() {
+        let to_world = Transform::new(1e-4, 0.0, 0.0, -1e-4, -80.0, 35.0);
+        let cases = [(Model::Affine, 2), (Model::Polynomial, 5)];
+        for (model, n) in cases {
+            let points = control_points(&to_world, Projection::Equirectangular, &MAP[..n]);
+            let err = Fit::new(model, points, Projection::Equirectangular)
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains("at least"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn rejects_degenerate_control_points() {
+        let to_world = Transform::new(1e-4, 0.0, 0.0, -1e-4, -80.0, 35.0);
+        let cases = [
+            (
+                [(0.0, 0.0), (100.0, 50.0), (200.0, 100.0), (300.0, 150.0)],
+                "in a line",
+            ),
+            ([(10.0, 10.0); 4], "same place"),
+        ];
+        for (map, want) in cases {
+            let points = control_points(&to_world, Projection::Equirectangular, &map);
+            let err = Fit::new(Model::Affine, points, Projection::Equirectangular)
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains(want), "{}", err);
+        }
+    }
+}
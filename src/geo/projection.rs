@@ -0,0 +1,269 @@
+use std::{
+    error::Error,
+    f64::consts::{FRAC_PI_2, FRAC_PI_4},
+    fmt,
+    str::FromStr,
+};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{de, ser};
+
+use super::LatLng;
+use crate::Point;
+
+lazy_static! {
+    static ref UTM_PATTERN: Regex = Regex::new(r"^utm\s*:?\s*(\d{1,2})\s*([ns])?$").unwrap();
+    static ref EPSG_PATTERN: Regex = Regex::new(r"^epsg\s*:\s*(\d+)$").unwrap();
+}
+
+// WGS 84 ellipsoid.
+const SEMI_MAJOR_AXIS: f64 = 6_378_137.0;
+const FLATTENING: f64 = 1.0 / 298.257_223_563;
+
+// UTM grid parameters.
+const SCALE_FACTOR: f64 = 0.9996;
+const FALSE_EASTING: f64 = 500_000.0;
+const FALSE_NORTHING_SOUTH: f64 = 10_000_000.0;
+
+/// The projection a map was drawn in. Projecting a location gives a point in
+/// the projection's own coordinates, which are degrees for equirectangular and
+/// meters for the others, with y growing to the north.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Projection {
+    #[default]
+    Equirectangular,
+    WebMercator,
+    Utm {
+        zone: u8,
+        north: bool,
+    },
+}
+
+impl Projection {
+    pub fn from_arg(s: &str) -> Result<Self, String> {
+        s.parse()
+            .map_err(|e| format!("invalid projection: {}: {}", s, e))
+    }
+
+    // The UTM zone that contains the location.
+    pub fn utm_for(location: &LatLng) -> Self {
+        let zone = (((location.lng + 180.0) / 6.0).floor() as i64).rem_euclid(60) + 1;
+        Projection::Utm {
+            zone: zone as u8,
+            north: location.lat >= 0.0,
+        }
+    }
+
+    pub fn project(&self, location: &LatLng) -> Point {
+        match *self {
+            Projection::Equirectangular => Point::from_xy(location.lng, location.lat),
+            Projection::WebMercator => {
+                let lat = location
+                    .lat
+                    .clamp(-85.051_128_78, 85.051_128_78)
+                    .to_radians();
+                Point::from_xy(
+                    SEMI_MAJOR_AXIS * location.lng.to_radians(),
+                    SEMI_MAJOR_AXIS * (FRAC_PI_4 + lat / 2.0).tan().ln(),
+                )
+            }
+            Projection::Utm { zone, north } => {
+                let (x, y) = Krueger::new().forward(
+                    location.lat.to_radians(),
+                    (location.lng - central_meridian(zone)).to_radians(),
+                );
+                Point::from_xy(
+                    FALSE_EASTING + SCALE_FACTOR * x,
+                    false_northing(north) + SCALE_FACTOR * y,
+                )
+            }
+        }
+    }
+
+    pub fn unproject(&self, p: &Point) -> LatLng {
+        match *self {
+            Projection::Equirectangular => LatLng::new(p.y(), p.x()),
+            Projection::WebMercator => LatLng::new(
+                (2.0 * (p.y() / SEMI_MAJOR_AXIS).exp().atan() - FRAC_PI_2).to_degrees(),
+                (p.x() / SEMI_MAJOR_AXIS).to_degrees(),
+            ),
+            Projection::Utm { zone, north } => {
+                let (lat, dlng) = Krueger::new().inverse(
+                    (p.x() - FALSE_EASTING) / SCALE_FACTOR,
+                    (p.y() - false_northing(north)) / SCALE_FACTOR,
+                );
+                LatLng::new(lat.to_degrees(), central_meridian(zone) + dlng.to_degrees())
+            }
+        }
+    }
+}
+
+fn central_meridian(zone: u8) -> f64 {
+    zone as f64 * 6.0 - 183.0
+}
+
+fn false_northing(north: bool) -> f64 {
+    if north {
+        0.0
+    } else {
+        FALSE_NORTHING_SOUTH
+    }
+}
+
+// The transverse Mercator projection of the ellipsoid using Krüger's series
+// to third order in the third flattening, which is good to well under a
+// millimeter within a UTM zone.
+struct Krueger {
+    n: f64,
+    a: f64,
+    alpha: [f64; 3],
+    beta: [f64; 3],
+    delta: [f64; 3],
+}
+
+impl Krueger {
+    fn new() -> Self {
+        let n = FLATTENING / (2.0 - FLATTENING);
+        let (n2, n3) = (n * n, n * n * n);
+        Self {
+            n,
+            a: SEMI_MAJOR_AXIS / (1.0 + n) * (1.0 + n2 / 4.0 + n2 * n2 / 64.0),
+            alpha: [
+                n / 2.0 - 2.0 * n2 / 3.0 + 5.0 * n3 / 16.0,
+                13.0 * n2 / 48.0 - 3.0 * n3 / 5.0,
+                61.0 * n3 / 240.0,
+            ],
+            beta: [
+                n / 2.0 - 2.0 * n2 / 3.0 + 37.0 * n3 / 96.0,
+                n2 / 48.0 + n3 / 15.0,
+                17.0 * n3 / 480.0,
+            ],
+            delta: [
+                2.0 * n - 2.0 * n2 / 3.0 - 2.0 * n3,
+                7.0 * n2 / 3.0 - 8.0 * n3 / 5.0,
+                56.0 * n3 / 15.0,
+            ],
+        }
+    }
+
+    // Maps latitude and longitude from the central meridian, both in
+    // radians, to unscaled easting and northing.
+    fn forward(&self, lat: f64, dlng: f64) -> (f64, f64) {
+        let e = 2.0 * self.n.sqrt() / (1.0 + self.n);
+        let t = (lat.sin().atanh() - e * (e * lat.sin()).atanh()).sinh();
+        let xi = (t / dlng.cos()).atan();
+        let eta = (dlng.sin() / (1.0 + t * t).sqrt()).atanh();
+        let (mut x, mut y) = (eta, xi);
+        for (j, alpha) in (1..).zip(self.alpha) {
+            let j = 2.0 * j as f64;
+            x += alpha * (j * xi).cos() * (j * eta).sinh();
+            y += alpha * (j * xi).sin() * (j * eta).cosh();
+        }
+        (self.a * x, self.a * y)
+    }
+
+    fn inverse(&self, x: f64, y: f64) -> (f64, f64) {
+        let (xi, eta) = (y / self.a, x / self.a);
+        let (mut xi_, mut eta_) = (xi, eta);
+        for (j, beta) in (1..).zip(self.beta) {
+            let j = 2.0 * j as f64;
+            xi_ -= beta * (j * xi).sin() * (j * eta).cosh();
+            eta_ -= beta * (j * xi).cos() * (j * eta).sinh();
+        }
+        let chi = (xi_.sin() / eta_.cosh()).asin();
+        let mut lat = chi;
+        for (j, delta) in (1..).zip(self.delta) {
+            lat += delta * (2.0 * j as f64 * chi).sin();
+        }
+        (lat, eta_.sinh().atan2(xi_.cos()))
+    }
+}
+
+impl FromStr for Projection {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        match s.as_str() {
+            "equirectangular" | "latlng" | "plate-carree" => {
+                return Ok(Projection::Equirectangular)
+            }
+            "web-mercator" | "webmercator" => return Ok(Projection::WebMercator),
+            _ => {}
+        }
+
+        let utm = |zone: u32, north: bool| {
+            if (1..=60).contains(&zone) {
+                Ok(Projection::Utm {
+                    zone: zone as u8,
+                    north,
+                })
+            } else {
+                Err(format!("UTM zone out of range: {}", zone).into())
+            }
+        };
+
+        if let Some(caps) = UTM_PATTERN.captures(&s) {
+            return utm(
+                caps[1].parse()?,
+                caps.get(2).is_none_or(|h| h.as_str() == "n"),
+            );
+        }
+
+        match EPSG_PATTERN.captures(&s).map(|caps| caps[1].parse::<u32>()) {
+            Some(Ok(4326)) => Ok(Projection::Equirectangular),
+            Some(Ok(3857)) => Ok(Projection::WebMercator),
+            Some(Ok(code @ 32601..=32660)) => utm(code - 32600, true),
+            Some(Ok(code @ 32701..=32760)) => utm(code - 32700, false),
+            _ => Err(format!("unknown projection: {}", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for Projection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Projection::Equirectangular => write!(f, "equirectangular"),
+            Projection::WebMercator => write!(f, "web-mercator"),
+            Projection::Utm { zone, north } => {
+                write!(f, "utm:{}{}", zone, if *north { 'n' } else { 's' })
+            }
+        }
+    }
+}
+
+impl ser::Serialize for Projection {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        s.serialize_str(&self.to_string())
+    }
+}
+
+impl<'d> de::Deserialize<'d> for Projection {
+    fn deserialize<D>(de: D) -> Result<Projection, D::Error>
+    where
+        D: de::Deserializer<'d>,
+    {
+        de.deserialize_str(ProjectionVisitor)
+    }
+}
+
+struct ProjectionVisitor;
+
+impl<'d> de::Visitor<'d> for ProjectionVisitor {
+    type Value = Projection;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a string")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Projection::from_str(v).map_err(|e| E::custom(e.to_string()))
+    }
+}
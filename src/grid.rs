@@ -0,0 +1,419 @@
+use std::collections::HashMap;
+
+use crate::{
+    extract::{Contour, Kind},
+    Path, Point,
+};
+
+/// A regular grid of samples stored by row, where missing samples are NaN.
+#[derive(Debug, Clone)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    values: Vec<f64>,
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize, values: Vec<f64>) -> Self {
+        assert_eq!(values.len(), width * height);
+        Self {
+            width,
+            height,
+            values,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, col: usize, row: usize) -> f64 {
+        self.values[row * self.width + col]
+    }
+
+    // The smallest and largest samples, ignoring missing ones.
+    pub fn range(&self) -> Option<(f64, f64)> {
+        self.values
+            .iter()
+            .filter(|v| !v.is_nan())
+            .fold(None, |r, &v| match r {
+                None => Some((v, v)),
+                Some((lo, hi)) => Some((lo.min(v), hi.max(v))),
+            })
+    }
+
+    // Traces the isolines at level with marching squares. Lines are in grid
+    // coordinates, where a sample's column and row are its x and y, and each
+    // comes with whether it closes on itself. Cells with a missing sample are
+    // skipped, so lines end at holes and at the edges of the grid.
+    pub fn contours(&self, level: f64) -> Vec<(Vec<Point>, bool)> {
+        let mut segments = Vec::new();
+        for row in 0..self.height.saturating_sub(1) {
+            for col in 0..self.width.saturating_sub(1) {
+                self.cell_segments(col, row, level, &mut segments);
+            }
+        }
+        join(segments)
+            .into_iter()
+            .map(|(edges, closed)| {
+                let points = edges
+                    .iter()
+                    .map(|e| self.crossing(e, level))
+                    .collect::<Vec<_>>();
+                (points, closed)
+            })
+            .collect()
+    }
+
+    // Traces contours at every multiple of interval, with every index_every-th
+    // one an index contour, scaling grid coordinates into map ones.
+    pub fn to_contours(
+        &self,
+        interval: f64,
+        index_every: usize,
+        scale: (f64, f64),
+    ) -> Vec<Contour> {
+        let Some((lo, hi)) = self.range() else {
+            return Vec::new();
+        };
+        let first = (lo / interval).ceil() as i64;
+        let last = (hi / interval).floor() as i64;
+        (first..=last)
+            .flat_map(|k| {
+                let level = k as f64 * interval;
+                let kind = if k.rem_euclid(index_every.max(1) as i64) == 0 {
+                    Kind::Index
+                } else {
+                    Kind::Intermediate
+                };
+                self.contours(level)
+                    .into_iter()
+                    .filter(|(line, _)| line.len() > 1)
+                    .map(move |(line, closed)| {
+                        let line = line
+                            .iter()
+                            .map(|p| Point::from_xy(p.x() * scale.0, p.y() * scale.1))
+                            .collect::<Vec<_>>();
+                        Contour {
+                            path: Path::from_points(&line, closed),
+                            kind,
+                            elevation: Some(level),
+                        }
+                    })
+            })
+            .collect()
+    }
+
+    fn cell_segments(&self, col: usize, row: usize, level: f64, out: &mut Vec<(Edge, Edge)>) {
+        let (tl, tr, br, bl) = (
+            self.get(col, row),
+            self.get(col + 1, row),
+            self.get(col + 1, row + 1),
+            self.get(col, row + 1),
+        );
+        if [tl, tr, br, bl].iter().any(|v| v.is_nan()) {
+            return;
+        }
+
+        let top = Edge::Horizontal(col, row);
+        let bottom = Edge::Horizontal(col, row + 1);
+        let left = Edge::Vertical(col, row);
+        let right = Edge::Vertical(col + 1, row);
+
+        let above = |v: f64| v >= level;
+        let case = (above(tl) as u8) << 3
+            | (above(tr) as u8) << 2
+            | (above(br) as u8) << 1
+            | above(bl) as u8;
+        match case {
+            0 | 15 => {}
+            1 | 14 => out.push((left, bottom)),
+            2 | 13 => out.push((bottom, right)),
+            3 | 12 => out.push((left, right)),
+            4 | 11 => out.push((top, right)),
+            6 | 9 => out.push((top, bottom)),
+            7 | 8 => out.push((left, top)),
+            // Saddles are resolved by the average of the corners.
+            5 | 10 => {
+                let center_above = above((tl + tr + br + bl) / 4.0);
+                if (case == 5) == center_above {
+                    out.push((left, top));
+                    out.push((bottom, right));
+                } else {
+                    out.push((left, bottom));
+                    out.push((top, right));
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // Where the isoline crosses an edge, interpolated between its samples.
+    fn crossing(&self, edge: &Edge, level: f64) -> Point {
+        let (a, b, from) = match *edge {
+            Edge::Horizontal(col, row) => (
+                self.get(col, row),
+                self.get(col + 1, row),
+                Point::from_xy(col as f64, row as f64),
+            ),
+            Edge::Vertical(col, row) => (
+                self.get(col, row),
+                self.get(col, row + 1),
+                Point::from_xy(col as f64, row as f64),
+            ),
+        };
+        let t = if a == b {
+            0.5
+        } else {
+            ((level - a) / (b - a)).clamp(0.0, 1.0)
+        };
+        match edge {
+            Edge::Horizontal(..) => Point::from_xy(from.x() + t, from.y()),
+            Edge::Vertical(..) => Point::from_xy(from.x(), from.y() + t),
+        }
+    }
+}
+
+// An edge between two adjacent samples, named by its top or left sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Edge {
+    Horizontal(usize, usize),
+    Vertical(usize, usize),
+}
+
+// Chains segments that share edges into lines. Every edge is crossed by at
+// most two segments, one from each of the cells on either side of it.
+fn join(segments: Vec<(Edge, Edge)>) -> Vec<(Vec<Edge>, bool)> {
+    let mut by_edge: HashMap<Edge, Vec<usize>> = HashMap::new();
+    for (i, (a, b)) in segments.iter().enumerate() {
+        by_edge.entry(*a).or_default().push(i);
+        by_edge.entry(*b).or_default().push(i);
+    }
+
+    let mut used = vec![false; segments.len()];
+    let walk = |start: usize, from: Edge, used: &mut Vec<bool>| -> (Vec<Edge>, bool) {
+        let mut line = vec![from];
+        let (mut ix, mut at) = (start, from);
+        loop {
+            used[ix] = true;
+            let (a, b) = segments[ix];
+            at = if a == at { b } else { a };
+            line.push(at);
+            match by_edge[&at].iter().find(|&&j| !used[j]) {
+                Some(&j) => ix = j,
+                None => {
+                    let closed = at == from && line.len() > 2;
+                    return (line, closed);
+                }
+            }
+        }
+    };
+
+    // Open lines start at edges only one segment touches, then whatever is
+    // left over is made of loops.
+    let mut lines = Vec::new();
+    for i in 0..segments.len() {
+        if used[i] {
+            continue;
+        }
+        let (a, b) = segments[i];
+        if by_edge[&a].len() == 1 {
+            lines.push(walk(i, a, &mut used));
+        } else if by_edge[&b].len() == 1 {
+            lines.push(walk(i, b, &mut used));
+        }
+    }
+    for i in 0..segments.len() {
+        if !used[i] {
+            let from = segments[i].0;
+            lines.push(walk(i, from, &mut used));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(x: f64, y: f64) -> Point {
+        Point::from_xy(x, y)
+    }
+
+    // The segments of the one cell of a 2x2 grid, whose corners are given
+    // clockwise from the top left.
+    fn segments(corners: [f64; 4], level: f64) -> Vec<(Edge, Edge)> {
+        let [tl, tr, br, bl] = corners;
+        let grid = Grid::new(2, 2, vec![tl, tr, bl, br]);
+        let mut out = Vec::new();
+        grid.cell_segments(0, 0, level, &mut out);
+        out
+    }
+
+    #[test]
+    fn each_case_crosses_the_edges_between_high_and_low_corners() {
+        let top = Edge::Horizontal(0, 0);
+        let bottom = Edge::Horizontal(0, 1);
+        let left = Edge::Vertical(0, 0);
+        let right = Edge::Vertical(1, 0);
+        // Cases are numbered by the corners at or above the level, with the
+        // top left as the high bit and going clockwise.
+        let cases = [
+            (0, vec![]),
+            (1, vec![(left, bottom)]),
+            (2, vec![(bottom, right)]),
+            (3, vec![(left, right)]),
+            (4, vec![(top, right)]),
+            (5, vec![(left, top), (bottom, right)]),
+            (6, vec![(top, bottom)]),
+            (7, vec![(left, top)]),
+            (8, vec![(left, top)]),
+            (9, vec![(top, bottom)]),
+            (10, vec![(left, bottom), (top, right)]),
+            (11, vec![(top, right)]),
+            (12, vec![(left, right)]),
+            (13, vec![(bottom, right)]),
+            (14, vec![(left, bottom)]),
+            (15, vec![]),
+        ];
+        for (case, want) in cases {
+            let corners = [3, 2, 1, 0].map(|bit| ((case >> bit) & 1) as f64);
+            assert_eq!(segments(corners, 0.5), want, "case {}", case);
+        }
+    }
+
+    #[test]
+    fn saddles_follow_the_center() {
+        let top = Edge::Horizontal(0, 0);
+        let bottom = Edge::Horizontal(0, 1);
+        let left = Edge::Vertical(0, 0);
+        let right = Edge::Vertical(1, 0);
+        // The high corners are joined through the center when it's above the
+        // level, cutting off the low ones, and kept apart when it's below.
+        assert_eq!(
+            segments([0.0, 2.0, 0.0, 2.0], 0.9),
+            vec![(left, top), (bottom, right)]
+        );
+        assert_eq!(
+            segments([0.0, 2.0, 0.0, 2.0], 1.1),
+            vec![(left, bottom), (top, right)]
+        );
+        assert_eq!(
+            segments([2.0, 0.0, 2.0, 0.0], 0.9),
+            vec![(left, bottom), (top, right)]
+        );
+        assert_eq!(
+            segments([2.0, 0.0, 2.0, 0.0], 1.1),
+            vec![(left, top), (bottom, right)]
+        );
+    }
+
+    #[test]
+    fn join_chains_segments_into_lines() {
+        let h = Edge::Horizontal;
+        let v = Edge::Vertical;
+        // An open line across three cells, given out of order and with one
+        // segment reversed, and a loop around a single sample.
+        let segments = vec![
+            (v(1, 0), v(2, 0)),
+            (h(5, 5), v(6, 5)),
+            (v(0, 0), v(1, 0)),
+            (v(6, 5), h(5, 6)),
+            (v(3, 0), v(2, 0)),
+            (h(5, 6), v(5, 5)),
+            (v(5, 5), h(5, 5)),
+        ];
+        let mut lines = join(segments);
+        assert_eq!(lines.len(), 2);
+        let (mut open, closed) = lines.remove(0);
+        assert!(!closed);
+        if open[0] != v(0, 0) {
+            open.reverse();
+        }
+        assert_eq!(open, vec![v(0, 0), v(1, 0), v(2, 0), v(3, 0)]);
+        let (ring, closed) = lines.remove(0);
+        assert!(closed);
+        assert_eq!(ring.len(), 5);
+        assert_eq!(ring.first(), ring.last());
+    }
+
+    #[test]
+    fn traces_a_closed_line_around_a_peak() {
+        #[rustfmt::skip]
+        let grid = Grid::new(3, 3, vec![
+            0.0, 0.0, 0.0,
+            0.0, 4.0, 0.0,
+            0.0, 0.0, 0.0,
+        ]);
+        let lines = grid.contours(1.0);
+        assert_eq!(lines.len(), 1);
+        let (points, closed) = &lines[0];
+        assert!(closed);
+        assert_eq!(points.len(), 5);
+        assert_eq!(points.first(), points.last());
+        for q in [p(1.0, 0.25), p(1.75, 1.0), p(1.0, 1.75), p(0.25, 1.0)] {
+            assert!(points.contains(&q), "{:?} not in {:?}", q, points);
+        }
+    }
+
+    #[test]
+    fn traces_open_lines_to_the_edges_and_holes() {
+        #[rustfmt::skip]
+        let grid = Grid::new(3, 3, vec![
+            0.0, 1.0, 2.0,
+            0.0, 1.0, 2.0,
+            0.0, 1.0, 2.0,
+        ]);
+        let lines = grid.contours(1.5);
+        assert_eq!(lines.len(), 1);
+        let (mut points, closed) = lines[0].clone();
+        assert!(!closed);
+        if points[0].y() != 0.0 {
+            points.reverse();
+        }
+        assert_eq!(points, vec![p(1.5, 0.0), p(1.5, 1.0), p(1.5, 2.0)]);
+
+        // A missing sample drops the cells around it, breaking the line.
+        #[rustfmt::skip]
+        let grid = Grid::new(3, 4, vec![
+            0.0, 1.0, 2.0,
+            0.0, 1.0, f64::NAN,
+            0.0, 1.0, 2.0,
+            0.0, 1.0, 2.0,
+        ]);
+        let lines = grid.contours(1.5);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].0.iter().all(|q| q.y() >= 2.0));
+    }
+
+    #[test]
+    fn to_contours_marks_index_levels() {
+        #[rustfmt::skip]
+        let grid = Grid::new(2, 2, vec![
+            0.0, 45.0,
+            0.0, 45.0,
+        ]);
+        let contours = grid.to_contours(10.0, 2, (2.0, 3.0));
+        let levels = contours
+            .iter()
+            .map(|c| (c.elevation.unwrap(), c.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            levels,
+            vec![
+                (10.0, Kind::Intermediate),
+                (20.0, Kind::Index),
+                (30.0, Kind::Intermediate),
+                (40.0, Kind::Index),
+            ]
+        );
+        // Lines are scaled into map coordinates.
+        let bounds = contours[1].path.bounds();
+        assert!((bounds.x() - 2.0 * 20.0 / 45.0).abs() < 1e-9);
+        assert_eq!(bounds.height(), 3.0);
+    }
+}
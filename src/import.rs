@@ -0,0 +1,406 @@
+use std::{error::Error, fmt, fs, io::BufReader, path, str::FromStr};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::{
+    elevation::{is_multiple, INDEX_EVERY},
+    extract::{self, Contour, Kind},
+    geo, shapefile, Path, Point, Rect, Size,
+};
+
+lazy_static! {
+    static ref PRJ_UTM_PATTERN: Regex =
+        Regex::new(r"utm[ _]zone[ _](\d{1,2})\s*,?\s*([ns])").unwrap();
+}
+
+// Attribute names commonly used for contour elevations, in order of
+// preference, when --elevation-property isn't given.
+const ELEVATION_PROPERTIES: &[&str] = &[
+    "elevation",
+    "elev",
+    "contourelevation",
+    "contourele",
+    "contour",
+    "elev_ft",
+    "elev_m",
+    "z",
+];
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap()]
+    src: String,
+
+    #[clap()]
+    dst: String,
+
+    #[clap(long, value_parser=ImportFormat::from_arg)]
+    format: Option<ImportFormat>,
+
+    #[clap(long, default_value_t = Size::new(4000.0, 4000.0), value_parser = Size::from_arg)]
+    size: Size,
+
+    #[clap(long, value_parser=geo::Projection::from_arg)]
+    projection: Option<geo::Projection>,
+
+    #[clap(long, value_parser=geo::Projection::from_arg)]
+    source_projection: Option<geo::Projection>,
+
+    #[clap(long)]
+    elevation_property: Option<String>,
+
+    #[clap(long)]
+    contour_interval: Option<f64>,
+}
+
+impl Args {
+    fn format(&self) -> Result<ImportFormat, Box<dyn Error>> {
+        self.format
+            .or_else(|| ImportFormat::from_path(&self.src))
+            .ok_or_else(|| format!("unknown format for {}, use --format", self.src).into())
+    }
+
+    // Shapefiles describe their coordinate system in a .prj file, GeoJSON is
+    // always longitude and latitude.
+    fn source_projection(&self, format: ImportFormat) -> Result<geo::Projection, Box<dyn Error>> {
+        if let Some(projection) = self.source_projection {
+            return Ok(projection);
+        }
+        let prj = path::Path::new(&self.src).with_extension("prj");
+        match format {
+            ImportFormat::Shapefile if prj.exists() => {
+                projection_from_prj(&fs::read_to_string(prj)?)
+            }
+            _ => Ok(geo::Projection::Equirectangular),
+        }
+    }
+}
+
+fn projection_from_prj(wkt: &str) -> Result<geo::Projection, Box<dyn Error>> {
+    let wkt = wkt.to_ascii_lowercase();
+    if let Some(caps) = PRJ_UTM_PATTERN.captures(&wkt) {
+        format!("utm:{}{}", &caps[1], &caps[2]).parse()
+    } else if wkt.contains("pseudo") && wkt.contains("mercator")
+        || wkt.contains("mercator_auxiliary_sphere")
+    {
+        Ok(geo::Projection::WebMercator)
+    } else if wkt.trim_start().starts_with("geogcs") {
+        Ok(geo::Projection::Equirectangular)
+    } else {
+        Err("unsupported projection in .prj, use --source-projection".into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    GeoJson,
+    Shapefile,
+}
+
+impl ImportFormat {
+    pub fn from_arg(s: &str) -> Result<Self, String> {
+        s.parse()
+            .map_err(|_| format!("invalid import format: {}", s))
+    }
+
+    pub fn from_path(path: &str) -> Option<Self> {
+        path::Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| match ext.to_ascii_lowercase().as_str() {
+                "json" | "geojson" => Some(ImportFormat::GeoJson),
+                "shp" => Some(ImportFormat::Shapefile),
+                _ => None,
+            })
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "geojson" => Ok(ImportFormat::GeoJson),
+            "shapefile" | "shp" => Ok(ImportFormat::Shapefile),
+            _ => Err(format!("unknown import format: {}", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportFormat::GeoJson => write!(f, "geojson"),
+            ImportFormat::Shapefile => write!(f, "shapefile"),
+        }
+    }
+}
+
+// The lines of a contour in source coordinates, each with whether it's a
+// closed ring.
+struct Feature {
+    lines: Vec<(Vec<Point>, bool)>,
+    z: Option<f64>,
+    properties: Map<String, Value>,
+}
+
+impl From<shapefile::Shape> for Feature {
+    fn from(shape: shapefile::Shape) -> Self {
+        Feature {
+            lines: shape.parts.into_iter().map(|p| (p, shape.closed)).collect(),
+            z: shape.z,
+            properties: shape.attributes,
+        }
+    }
+}
+
+fn read_geojson(src: &str) -> Result<Vec<Feature>, Box<dyn Error>> {
+    let json: Value = serde_json::from_reader(BufReader::new(fs::File::open(src)?))?;
+    let features = match json["type"].as_str() {
+        Some("FeatureCollection") => json["features"]
+            .as_array()
+            .ok_or("no features in feature collection")?
+            .iter()
+            .collect(),
+        Some("Feature") => vec![&json],
+        _ => vec![],
+    };
+
+    let mut result = Vec::new();
+    for f in features {
+        let mut feature = Feature {
+            lines: Vec::new(),
+            z: None,
+            properties: f["properties"].as_object().cloned().unwrap_or_default(),
+        };
+        add_geometry(&mut feature, &f["geometry"])?;
+        result.push(feature);
+    }
+
+    // A bare geometry is a single feature without properties.
+    if result.is_empty() && json.get("coordinates").is_some() {
+        let mut feature = Feature {
+            lines: Vec::new(),
+            z: None,
+            properties: Map::new(),
+        };
+        add_geometry(&mut feature, &json)?;
+        result.push(feature);
+    }
+    Ok(result)
+}
+
+fn add_geometry(feature: &mut Feature, geometry: &Value) -> Result<(), Box<dyn Error>> {
+    let coords = &geometry["coordinates"];
+    let mut line = |coords: &Value, closed: bool| -> Result<(), Box<dyn Error>> {
+        let points = coords
+            .as_array()
+            .ok_or("invalid coordinates")?
+            .iter()
+            .map(|c| {
+                let x = c[0].as_f64().ok_or("invalid position")?;
+                let y = c[1].as_f64().ok_or("invalid position")?;
+                if feature.z.is_none() {
+                    feature.z = c.get(2).and_then(Value::as_f64);
+                }
+                Ok(Point::from_xy(x, y))
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+        feature.lines.push((points, closed));
+        Ok(())
+    };
+    let each = |v: &Value| v.as_array().cloned().unwrap_or_default();
+
+    match geometry["type"].as_str() {
+        Some("LineString") => line(coords, false)?,
+        Some("MultiLineString") => {
+            for l in each(coords) {
+                line(&l, false)?;
+            }
+        }
+        Some("Polygon") => {
+            for ring in each(coords) {
+                line(&ring, true)?;
+            }
+        }
+        Some("MultiPolygon") => {
+            for ring in each(coords).iter().flat_map(each) {
+                line(&ring, true)?;
+            }
+        }
+        Some("GeometryCollection") => {
+            for g in each(&geometry["geometries"]) {
+                add_geometry(feature, &g)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn elevation(feature: &Feature, property: Option<&str>) -> Option<f64> {
+    let find = |name: &str| {
+        feature
+            .properties
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .and_then(|(_, v)| match v {
+                Value::Number(n) => n.as_f64(),
+                Value::String(s) => s.trim().parse().ok(),
+                _ => None,
+            })
+    };
+    match property {
+        Some(name) => find(name),
+        None => ELEVATION_PROPERTIES
+            .iter()
+            .find_map(|name| find(name))
+            .or(feature.z),
+    }
+}
+
+// The smallest difference between distinct elevations, which is the contour
+// interval for any reasonably complete dataset.
+fn infer_interval(elevations: &[f64]) -> Option<f64> {
+    let mut elevations = elevations.to_vec();
+    elevations.sort_by(|a, b| a.total_cmp(b));
+    elevations
+        .windows(2)
+        .map(|w| w[1] - w[0])
+        .filter(|d| *d > 1e-6)
+        .min_by(|a, b| a.total_cmp(b))
+}
+
+fn bounds(points: impl Iterator<Item = Point>) -> Option<Rect> {
+    points
+        .map(|p| Rect::from_points(&p, &p))
+        .reduce(|a, b| a.union(&b))
+}
+
+pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let format = args.format()?;
+    let source = args.source_projection(format)?;
+    let features = match format {
+        ImportFormat::GeoJson => read_geojson(&args.src)?,
+        ImportFormat::Shapefile => shapefile::read(&args.src)?
+            .into_iter()
+            .map(Feature::from)
+            .collect(),
+    };
+
+    let locations = features
+        .iter()
+        .map(|f| {
+            f.lines
+                .iter()
+                .map(|(line, _)| line.iter().map(|p| source.unproject(p)).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    if let Some(ll) = locations
+        .iter()
+        .flatten()
+        .flatten()
+        .find(|ll| ll.lat.abs() > 90.0 || ll.lng.abs() > 180.0)
+    {
+        return Err(format!(
+            "({}, {}) isn't a longitude and latitude, use --source-projection",
+            ll.lng, ll.lat
+        )
+        .into());
+    }
+
+    let area = bounds(
+        locations
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|ll| Point::from_xy(ll.lng, ll.lat)),
+    )
+    .ok_or("no contours found")?;
+    let center = geo::LatLng::new(
+        area.y() + area.height() / 2.0,
+        area.x() + area.width() / 2.0,
+    );
+    let projection = args
+        .projection
+        .unwrap_or_else(|| geo::Projection::utm_for(&center));
+
+    // Fit the projected contours into the requested size, keeping their
+    // aspect ratio.
+    let world = locations
+        .iter()
+        .map(|lines| {
+            lines
+                .iter()
+                .map(|line| {
+                    line.iter()
+                        .map(|ll| projection.project(ll))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let extent = bounds(world.iter().flatten().flatten().copied()).ok_or("no contours found")?;
+    let scale = (args.size.width() / extent.width()).min(args.size.height() / extent.height());
+    if !scale.is_finite() {
+        return Err("contours have no extent".into());
+    }
+    let (top, left) = (extent.y() + extent.height(), extent.x());
+    let to_map = |p: &Point| Point::from_xy((p.x() - left) * scale, (top - p.y()) * scale);
+
+    let region = geo::Rect::new(
+        projection.unproject(&Point::from_xy(left, top)),
+        projection.unproject(&Point::from_xy(left + extent.width(), extent.y())),
+    );
+
+    let elevations = features
+        .iter()
+        .map(|f| elevation(f, args.elevation_property.as_deref()))
+        .collect::<Vec<_>>();
+    let interval = args
+        .contour_interval
+        .or_else(|| infer_interval(&elevations.iter().flatten().copied().collect::<Vec<_>>()));
+
+    let contours = features
+        .iter()
+        .zip(&world)
+        .zip(&elevations)
+        .flat_map(|((feature, lines), elevation)| {
+            feature
+                .lines
+                .iter()
+                .zip(lines)
+                .filter(|(_, line)| line.len() > 1)
+                .map(move |((_, closed), line)| Contour {
+                    path: Path::from_points(&line.iter().map(to_map).collect::<Vec<_>>(), *closed),
+                    kind: match (elevation, interval) {
+                        (Some(e), Some(i)) if is_multiple(*e, i * INDEX_EVERY) => Kind::Index,
+                        _ => Kind::Intermediate,
+                    },
+                    elevation: *elevation,
+                })
+        })
+        .collect::<Vec<_>>();
+
+    let data = extract::Data::new(
+        Size::new(extent.width() * scale, extent.height() * scale),
+        region,
+        projection,
+        None,
+        contours,
+    );
+    println!(
+        "{} contours, size = {}, region = {}, projection = {}",
+        data.contours.len(),
+        data.size,
+        data.region,
+        data.projection
+    );
+
+    serde_json::to_writer(&mut fs::File::create(&args.dst)?, &data)?;
+
+    Ok(())
+}
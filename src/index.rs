@@ -0,0 +1,281 @@
+use std::{
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{extract, Rect};
+
+// An index file is the magic bytes and a version followed by the length of
+// the data file it was built from, the number of items and then the tree's
+// boxes and item indices.
+const MAGIC: &[u8; 4] = b"TIDX";
+const VERSION: u8 = 2;
+
+const NODE_SIZE: usize = 16;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap()]
+    src: String,
+
+    #[clap(long)]
+    dst: Option<String>,
+}
+
+/// A static R-tree over the bounds of the contours, packed by sorting them
+/// along a Hilbert curve and grouping them NODE_SIZE at a time. The leaves
+/// come first in `boxes` and each level of parents follows the one below it.
+#[derive(Debug, Clone)]
+pub struct Index {
+    count: usize,
+    boxes: Vec<[f64; 4]>,
+    indices: Vec<u32>,
+    level_bounds: Vec<usize>,
+}
+
+impl Index {
+    pub fn build(bounds: &[Rect]) -> Self {
+        let count = bounds.len();
+        let mut boxes = bounds
+            .iter()
+            .map(|r| [r.x(), r.y(), r.x() + r.width(), r.y() + r.height()])
+            .collect::<Vec<_>>();
+        let mut indices = (0..count as u32).collect::<Vec<_>>();
+        let level_bounds = level_bounds(count);
+
+        let extent = boxes.iter().fold(
+            [
+                f64::INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ],
+            |a, b| union(&a, b),
+        );
+        let (w, h) = (extent[2] - extent[0], extent[3] - extent[1]);
+        let key = |b: &[f64; 4]| {
+            let n = ((1u32 << 16) - 1) as f64;
+            let x = if w > 0.0 {
+                n * ((b[0] + b[2]) / 2.0 - extent[0]) / w
+            } else {
+                0.0
+            };
+            let y = if h > 0.0 {
+                n * ((b[1] + b[3]) / 2.0 - extent[1]) / h
+            } else {
+                0.0
+            };
+            hilbert(x as u32, y as u32)
+        };
+        let mut order = (0..count).collect::<Vec<_>>();
+        order.sort_by_key(|&i| key(&boxes[i]));
+        boxes = order.iter().map(|&i| boxes[i]).collect();
+        indices = order.iter().map(|&i| indices[i]).collect();
+
+        // Each parent's index is the position of its first child.
+        let mut pos = 0;
+        for &end in &level_bounds[..level_bounds.len() - 1] {
+            while pos < end {
+                let first = pos;
+                let mut b = boxes[pos];
+                pos += 1;
+                while pos < end && pos - first < NODE_SIZE {
+                    b = union(&b, &boxes[pos]);
+                    pos += 1;
+                }
+                boxes.push(b);
+                indices.push(first as u32);
+            }
+        }
+
+        Self {
+            count,
+            boxes,
+            indices,
+            level_bounds,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    // The indices of the items whose bounds intersect rect, in order.
+    pub fn query(&self, rect: &Rect) -> Vec<usize> {
+        let q = [
+            rect.x(),
+            rect.y(),
+            rect.x() + rect.width(),
+            rect.y() + rect.height(),
+        ];
+        let mut found = Vec::new();
+        if self.count == 0 {
+            return found;
+        }
+
+        let mut stack = vec![(self.boxes.len() - 1, self.level_bounds.len() - 1)];
+        while let Some((node, level)) = stack.pop() {
+            let end = (node + NODE_SIZE).min(self.level_bounds[level]);
+            for pos in node..end {
+                let b = &self.boxes[pos];
+                if b[2] < q[0] || b[0] > q[2] || b[3] < q[1] || b[1] > q[3] {
+                    continue;
+                }
+                if pos < self.count {
+                    found.push(self.indices[pos] as usize);
+                } else {
+                    stack.push((self.indices[pos] as usize, level - 1));
+                }
+            }
+        }
+        found.sort_unstable();
+        found
+    }
+
+    // Writes the index for a data file that is src_len bytes long.
+    pub fn write<W: Write>(&self, mut w: W, src_len: u64) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_u8(VERSION)?;
+        w.write_u64::<BigEndian>(src_len)?;
+        w.write_u32::<BigEndian>(self.count as u32)?;
+        for b in &self.boxes {
+            for v in b {
+                w.write_f64::<BigEndian>(*v)?;
+            }
+        }
+        for i in &self.indices {
+            w.write_u32::<BigEndian>(*i)?;
+        }
+        Ok(())
+    }
+
+    // Reads an index along with the length of the data file it was built
+    // from.
+    pub fn read(mut r: &[u8]) -> Result<(Self, u64), Box<dyn Error>> {
+        if !r.starts_with(MAGIC) {
+            return Err("not an index file".into());
+        }
+        r = &r[MAGIC.len()..];
+        let version = r.read_u8()?;
+        if version != VERSION {
+            return Err(format!("unsupported index version: {}", version).into());
+        }
+        let src_len = r.read_u64::<BigEndian>()?;
+        let count = r.read_u32::<BigEndian>()? as usize;
+        let level_bounds = level_bounds(count);
+        let n = level_bounds.last().copied().unwrap_or(0);
+        // Each node is a box of four f64s and a u32 index.
+        if n.saturating_mul(36) > r.len() {
+            return Err("index file is truncated".into());
+        }
+        let mut boxes = Vec::with_capacity(n);
+        for _ in 0..n {
+            boxes.push([
+                r.read_f64::<BigEndian>()?,
+                r.read_f64::<BigEndian>()?,
+                r.read_f64::<BigEndian>()?,
+                r.read_f64::<BigEndian>()?,
+            ]);
+        }
+        let mut indices = Vec::with_capacity(n);
+        for _ in 0..n {
+            indices.push(r.read_u32::<BigEndian>()?);
+        }
+        Ok((
+            Self {
+                count,
+                boxes,
+                indices,
+                level_bounds,
+            },
+            src_len,
+        ))
+    }
+
+    // Where the index for a data file is cached.
+    pub fn path_for<P: AsRef<Path>>(src: P) -> PathBuf {
+        let mut name = src.as_ref().as_os_str().to_owned();
+        name.push(".idx");
+        PathBuf::from(name)
+    }
+
+    // Reads the cached index for a data file, as long as it isn't older than
+    // the data and was built from a file of the same length.
+    pub fn open<P: AsRef<Path>>(src: P) -> Option<Self> {
+        let path = Self::path_for(&src);
+        let src = fs::metadata(src).ok()?;
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        if modified < src.modified().ok()? {
+            return None;
+        }
+        let (index, src_len) = Self::read(&fs::read(path).ok()?).ok()?;
+        (src_len == src.len()).then_some(index)
+    }
+}
+
+// The end of each level of the tree in boxes, leaves first.
+fn level_bounds(count: usize) -> Vec<usize> {
+    let mut n = count;
+    let mut total = n;
+    let mut bounds = vec![total];
+    while n > 1 {
+        n = n.div_ceil(NODE_SIZE);
+        total += n;
+        bounds.push(total);
+    }
+    bounds
+}
+
+fn union(a: &[f64; 4], b: &[f64; 4]) -> [f64; 4] {
+    [
+        a[0].min(b[0]),
+        a[1].min(b[1]),
+        a[2].max(b[2]),
+        a[3].max(b[3]),
+    ]
+}
+
+// The distance of (x, y) along a Hilbert curve filling a 2^16 square.
+fn hilbert(x: u32, y: u32) -> u64 {
+    let (mut x, mut y) = (x as i64, y as i64);
+    let mut d = 0u64;
+    let n = 1i64 << 16;
+    let mut s = n >> 1;
+    while s > 0 {
+        let rx = (x & s > 0) as i64;
+        let ry = (y & s > 0) as i64;
+        d += (s * s) as u64 * ((3 * rx) ^ ry) as u64;
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s >>= 1;
+    }
+    d
+}
+
+pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let data = extract::Data::open(&args.src)?;
+    let index = data.index();
+    let dst = args
+        .dst
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Index::path_for(&args.src));
+    let mut w = io::BufWriter::new(fs::File::create(&dst)?);
+    index.write(&mut w, fs::metadata(&args.src)?.len())?;
+    w.flush()?;
+    println!("{} contours → {}", index.len(), dst.display());
+    Ok(())
+}
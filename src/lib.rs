@@ -1,22 +1,42 @@
 mod color;
+pub mod dem;
+mod elevation;
+pub mod export;
 pub mod extract;
+mod format;
+mod gallery;
 pub mod geo;
+mod grid;
+pub mod import;
+pub mod index;
+mod matcher;
+mod noise;
+pub mod pack;
 mod path;
+mod pdf;
 mod point;
 mod range;
 mod rect;
 pub mod render;
 pub mod render_many;
 mod seed;
+mod shapefile;
 mod size;
+pub mod synth;
 mod themes;
+mod tint;
 pub mod topo;
+mod transform;
 
 pub use color::Color;
-pub use path::Path;
+pub use format::Format;
+pub use matcher::Matcher;
+pub use path::{Cmd, Path};
 pub use point::Point;
 pub use range::Range;
 pub use rect::Rect;
 pub use seed::Seed;
 pub use size::Size;
 pub use themes::{ThemeRef, Themes};
+pub use tint::Fill;
+pub use transform::Transform;
@@ -2,7 +2,7 @@ use std::error::Error;
 
 use clap::{Parser, Subcommand};
 
-use topo::{extract, render, render_many};
+use topo::{dem, export, extract, import, index, pack, render, render_many, synth};
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -15,6 +15,12 @@ enum Command {
     Render(render::Args),
     RenderMany(render_many::Args),
     Extract(extract::Args),
+    Export(export::Args),
+    Import(import::Args),
+    Dem(dem::Args),
+    Synth(synth::Args),
+    Pack(pack::Args),
+    Index(index::Args),
 }
 
 impl Command {
@@ -23,6 +29,12 @@ impl Command {
             Self::Render(args) => render::run(args),
             Self::RenderMany(args) => render_many::run(args),
             Self::Extract(args) => extract::run(args),
+            Self::Export(args) => export::run(args),
+            Self::Import(args) => import::run(args),
+            Self::Dem(args) => dem::run(args),
+            Self::Synth(args) => synth::run(args),
+            Self::Pack(args) => pack::run(args),
+            Self::Index(args) => index::run(args),
         }
     }
 }
@@ -0,0 +1,151 @@
+use std::{error::Error, fs, path};
+
+use serde::Deserialize;
+
+use crate::{extract::Kind, Color, Path, Range};
+
+// Stroke widths read from a PDF are scaled by the transforms they are drawn
+// under, so they are matched to within this fraction of their width.
+const STROKE_WIDTH_TOLERANCE: f64 = 1e-6;
+
+#[derive(Debug, Clone, Default)]
+pub struct Style {
+    pub stroke: Option<Color>,
+    pub stroke_width: Option<f64>,
+    pub fill: Option<String>,
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+    pub groups: Vec<String>,
+}
+
+// A path accepted by a matcher along with what its rule and style say about
+// the kind of contour it is.
+#[derive(Debug)]
+pub struct Match {
+    pub path: Path,
+    pub kind: Option<Kind>,
+    pub stroke_width: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    #[serde(default)]
+    pub stroke: Option<Color>,
+
+    #[serde(default)]
+    pub stroke_tolerance: f64,
+
+    #[serde(default)]
+    pub stroke_width: Option<Range>,
+
+    #[serde(default)]
+    pub fill: Option<String>,
+
+    #[serde(default)]
+    pub class: Option<String>,
+
+    #[serde(default)]
+    pub id: Option<String>,
+
+    #[serde(default)]
+    pub parent: Option<String>,
+
+    #[serde(default)]
+    pub kind: Option<Kind>,
+}
+
+impl Rule {
+    pub fn matches(&self, style: &Style) -> bool {
+        if let Some(stroke) = &self.stroke {
+            match &style.stroke {
+                Some(c) if c.distance_to(stroke) <= self.stroke_tolerance => {}
+                _ => return false,
+            }
+        }
+
+        if let Some(width) = &self.stroke_width {
+            match style.stroke_width {
+                Some(w) if width.includes_approx(w, STROKE_WIDTH_TOLERANCE) => {}
+                _ => return false,
+            }
+        }
+
+        if let Some(fill) = &self.fill {
+            match &style.fill {
+                Some(f) if is_same_paint(f, fill) => {}
+                _ => return false,
+            }
+        }
+
+        if let Some(class) = &self.class {
+            if !style.classes.iter().any(|c| c == class) {
+                return false;
+            }
+        }
+
+        if let Some(id) = &self.id {
+            if style.id.as_ref() != Some(id) {
+                return false;
+            }
+        }
+
+        if let Some(parent) = &self.parent {
+            if !style.groups.iter().any(|g| g == parent) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+fn is_same_paint(a: &str, b: &str) -> bool {
+    match (a.parse::<Color>(), b.parse::<Color>()) {
+        (Ok(a), Ok(b)) => a.distance_to(&b) == 0.0,
+        _ => a.trim().eq_ignore_ascii_case(b.trim()),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    rules: Vec<Rule>,
+}
+
+impl Matcher {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self { rules }
+    }
+
+    pub fn open<P: AsRef<path::Path>>(src: P) -> Result<Self, Box<dyn Error>> {
+        let rules: Vec<Rule> = serde_json::from_reader(&mut fs::File::open(src)?)?;
+        if rules.is_empty() {
+            return Err("no rules".into());
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn matches(&self, style: &Style) -> bool {
+        self.find(style).is_some()
+    }
+
+    pub fn find(&self, style: &Style) -> Option<&Rule> {
+        self.rules.iter().find(|r| r.matches(style))
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+}
+
+impl Default for Matcher {
+    // The style pdftocairo produces for contours on the Uwharrie quads.
+    fn default() -> Self {
+        Self::new(vec![Rule {
+            stroke: Some(Color::from_rgb(178, 178, 178)),
+            stroke_width: Some(Range::from(0.99001..0.99001)),
+            fill: Some(String::from("none")),
+            ..Default::default()
+        }])
+    }
+}
@@ -0,0 +1,93 @@
+use rand::{seq::SliceRandom, Rng};
+
+/// Two dimensional gradient noise over a shuffled permutation table, so the
+/// same rng state always gives the same field.
+pub struct Noise {
+    perm: [u8; 512],
+}
+
+impl Noise {
+    pub fn new<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let mut table = [0u8; 256];
+        for (i, v) in table.iter_mut().enumerate() {
+            *v = i as u8;
+        }
+        table.shuffle(rng);
+        let mut perm = [0u8; 512];
+        for (i, v) in perm.iter_mut().enumerate() {
+            *v = table[i & 255];
+        }
+        Self { perm }
+    }
+
+    // Noise in roughly [-1, 1] that is zero at integer coordinates.
+    pub fn at(&self, x: f64, y: f64) -> f64 {
+        let (xf, yf) = (x.floor(), y.floor());
+        let (xi, yi) = ((xf as i64 & 255) as usize, (yf as i64 & 255) as usize);
+        let (x, y) = (x - xf, y - yf);
+        let (u, v) = (fade(x), fade(y));
+
+        let hash = |i: usize, j: usize| self.perm[self.perm[i] as usize + j];
+        let n00 = grad(hash(xi, yi), x, y);
+        let n10 = grad(hash(xi + 1, yi), x - 1.0, y);
+        let n01 = grad(hash(xi, yi + 1), x, y - 1.0);
+        let n11 = grad(hash(xi + 1, yi + 1), x - 1.0, y - 1.0);
+        lerp(lerp(n00, n10, u), lerp(n01, n11, u), v)
+    }
+
+    // Fractional Brownian motion: octaves of noise, each at twice the
+    // frequency and half the amplitude of the last.
+    pub fn fbm(&self, x: f64, y: f64, octaves: usize) -> f64 {
+        let (mut sum, mut amp, mut freq) = (0.0, 0.5, 1.0);
+        for _ in 0..octaves {
+            sum += amp * self.at(x * freq, y * freq);
+            amp *= 0.5;
+            freq *= 2.0;
+        }
+        sum
+    }
+
+    // Ridged multifractal noise, where the creases of folded noise become
+    // sharp ridges and each octave is damped where the last one was low.
+    pub fn ridged(&self, x: f64, y: f64, octaves: usize) -> f64 {
+        let (mut sum, mut amp, mut freq, mut weight) = (0.0, 0.5, 1.0, 1.0);
+        for _ in 0..octaves {
+            let n = 1.0 - self.at(x * freq, y * freq).abs();
+            let n = n * n * weight;
+            weight = (n * 2.0).clamp(0.0, 1.0);
+            sum += amp * n;
+            amp *= 0.5;
+            freq *= 2.0;
+        }
+        sum
+    }
+
+    // fBm sampled at coordinates displaced by more fBm, which twists the
+    // terrain into flowing shapes.
+    pub fn warped(&self, x: f64, y: f64, octaves: usize) -> f64 {
+        let qx = self.fbm(x, y, octaves);
+        let qy = self.fbm(x + 5.2, y + 1.3, octaves);
+        self.fbm(x + 4.0 * qx, y + 4.0 * qy, octaves)
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+fn grad(hash: u8, x: f64, y: f64) -> f64 {
+    match hash & 7 {
+        0 => x + y,
+        1 => x - y,
+        2 => -x + y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
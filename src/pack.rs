@@ -0,0 +1,483 @@
+use std::{
+    error::Error,
+    fmt, fs,
+    io::{self, Read, Write},
+    str::FromStr,
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    extract::{self, Contour, Kind},
+    geo, Cmd, Path, Point, Size,
+};
+
+// A packed file starts with the magic bytes, a version and how the body is
+// compressed, followed by the length of a JSON header describing the map and
+// then the contours in the body.
+const MAGIC: &[u8; 4] = b"TOPO";
+const VERSION: u8 = 2;
+
+const OP_MOVE: u8 = 0;
+const OP_LINE: u8 = 1;
+const OP_CURVE: u8 = 2;
+const OP_QUAD: u8 = 3;
+const OP_CLOSE: u8 = 4;
+
+const KIND_INTERMEDIATE: u8 = 0;
+const KIND_INDEX: u8 = 1;
+const HAS_ELEVATION: u8 = 2;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap()]
+    src: String,
+
+    #[clap()]
+    dst: String,
+
+    #[clap(long, value_parser=Compression::from_arg, default_value_t)]
+    compression: Compression,
+
+    #[clap(long, default_value_t = 0.01)]
+    precision: f64,
+}
+
+// Compressed files are smaller, but only uncompressed ones can be read in
+// place from the mapped file rather than inflated into memory first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    None,
+    #[default]
+    Deflate,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_arg(s: &str) -> Result<Self, String> {
+        match s.parse() {
+            Ok(Compression::Zstd) if !cfg!(feature = "zstd") => {
+                Err("zstd support is not enabled, build with --features zstd".to_owned())
+            }
+            Ok(c) => Ok(c),
+            Err(_) => Err(format!("invalid compression: {}", s)),
+        }
+    }
+
+    fn from_byte(b: u8) -> Result<Self, Box<dyn Error>> {
+        match b {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Deflate),
+            2 => Ok(Compression::Zstd),
+            _ => Err(format!("unknown compression: {}", b).into()),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Deflate => 1,
+            Compression::Zstd => 2,
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Compression::None),
+            "deflate" => Ok(Compression::Deflate),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(format!("unknown compression: {}", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::None => write!(f, "none"),
+            Compression::Deflate => write!(f, "deflate"),
+            Compression::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    size: Size,
+    region: geo::Rect,
+    #[serde(default)]
+    projection: geo::Projection,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fit: Option<geo::Fit>,
+    precision: f64,
+    contours: usize,
+    // The length of the body once it is decompressed.
+    body: usize,
+}
+
+pub fn is_packed(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub fn write<W: Write>(
+    data: &extract::Data,
+    mut w: W,
+    compression: Compression,
+    precision: f64,
+) -> Result<(), Box<dyn Error>> {
+    if precision <= 0.0 {
+        return Err("precision must be positive".into());
+    }
+
+    let body = encode(&data.contours, precision)?;
+    let header = serde_json::to_vec(&Header {
+        size: data.size.clone(),
+        region: data.region.clone(),
+        projection: data.projection,
+        fit: data.fit.clone(),
+        precision,
+        contours: data.contours.len(),
+        body: body.len(),
+    })?;
+    w.write_all(MAGIC)?;
+    w.write_u8(VERSION)?;
+    w.write_u8(compression.to_byte())?;
+    w.write_u32::<BigEndian>(header.len() as u32)?;
+    w.write_all(&header)?;
+
+    match compression {
+        Compression::None => w.write_all(&body)?,
+        Compression::Deflate => {
+            let mut e = DeflateEncoder::new(w, flate2::Compression::best());
+            e.write_all(&body)?;
+            e.finish()?;
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => zstd::stream::copy_encode(&body[..], w, 19)?,
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => return Err("zstd support is not enabled".into()),
+    }
+    Ok(())
+}
+
+pub fn read(bytes: &[u8]) -> Result<extract::Data, Box<dyn Error>> {
+    if !is_packed(bytes) {
+        return Err("not a packed file".into());
+    }
+    let mut r = &bytes[MAGIC.len()..];
+    let version = r.read_u8()?;
+    if version != VERSION {
+        return Err(format!("unsupported version: {}", version).into());
+    }
+    let compression = Compression::from_byte(r.read_u8()?)?;
+    let len = r.read_u32::<BigEndian>()? as usize;
+    let header: Header = serde_json::from_slice(r.get(..len).ok_or("truncated header")?)?;
+    let body = &r[len..];
+
+    // Decompression stops just past the length in the header, so a small
+    // file can't inflate into more memory than it says it needs.
+    let inflate = |r: &mut dyn Read| -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut buf = Vec::new();
+        r.take(header.body as u64 + 1).read_to_end(&mut buf)?;
+        if buf.len() != header.body {
+            return Err("packed body doesn't match its header".into());
+        }
+        Ok(buf)
+    };
+    let contours = match compression {
+        Compression::None if body.len() == header.body => decode(body, &header)?,
+        Compression::None => return Err("packed body doesn't match its header".into()),
+        Compression::Deflate => decode(&inflate(&mut DeflateDecoder::new(body))?, &header)?,
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => decode(
+            &inflate(&mut zstd::stream::read::Decoder::new(body)?)?,
+            &header,
+        )?,
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => return Err("zstd support is not enabled".into()),
+    };
+
+    Ok(extract::Data::new(
+        header.size,
+        header.region,
+        header.projection,
+        header.fit,
+        contours,
+    ))
+}
+
+// Each contour is a byte of flags, its elevation if it has one, the number of
+// commands and then the commands, each an opcode followed by its points.
+// Coordinates are quantized to the precision and written as the zigzag
+// varint difference from the previous coordinate.
+fn encode(contours: &[Contour], precision: f64) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let mut last = (0i64, 0i64);
+    for contour in contours {
+        let mut flags = match contour.kind {
+            Kind::Intermediate => KIND_INTERMEDIATE,
+            Kind::Index => KIND_INDEX,
+        };
+        if contour.elevation.is_some() {
+            flags |= HAS_ELEVATION;
+        }
+        buf.write_u8(flags)?;
+        if let Some(e) = contour.elevation {
+            buf.write_f64::<BigEndian>(e)?;
+        }
+
+        let cmds = contour.path.cmds();
+        write_varint(&mut buf, cmds.len() as u64)?;
+        for cmd in cmds {
+            buf.write_u8(match cmd {
+                Cmd::MoveTo(_) => OP_MOVE,
+                Cmd::LineTo(_) => OP_LINE,
+                Cmd::CurveTo(..) => OP_CURVE,
+                Cmd::QuadTo(..) => OP_QUAD,
+                Cmd::Close => OP_CLOSE,
+            })?;
+            for p in cmd.points() {
+                let q = (
+                    (p.x() / precision).round() as i64,
+                    (p.y() / precision).round() as i64,
+                );
+                write_varint(&mut buf, zigzag(q.0 - last.0))?;
+                write_varint(&mut buf, zigzag(q.1 - last.1))?;
+                last = q;
+            }
+        }
+    }
+    Ok(buf)
+}
+
+fn decode(mut r: &[u8], header: &Header) -> Result<Vec<Contour>, Box<dyn Error>> {
+    let mut last = (0i64, 0i64);
+    let mut point = |r: &mut &[u8]| -> io::Result<Point> {
+        let step = |v: i64, d: u64| {
+            v.checked_add(unzigzag(d)).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "coordinate out of range")
+            })
+        };
+        last.0 = step(last.0, read_varint(r)?)?;
+        last.1 = step(last.1, read_varint(r)?)?;
+        Ok(Point::from_xy(
+            last.0 as f64 * header.precision,
+            last.1 as f64 * header.precision,
+        ))
+    };
+
+    // Counts come from the file, so they are checked against what's left of
+    // it before anything is allocated for them. Every contour and command
+    // takes at least a byte.
+    if header.contours > r.len() {
+        return Err("packed file is truncated".into());
+    }
+    let mut contours = Vec::with_capacity(header.contours);
+    for _ in 0..header.contours {
+        let flags = r.read_u8()?;
+        let elevation = if flags & HAS_ELEVATION != 0 {
+            Some(r.read_f64::<BigEndian>()?)
+        } else {
+            None
+        };
+        let n = read_varint(&mut r)? as usize;
+        if n > r.len() {
+            return Err("packed file is truncated".into());
+        }
+        let mut cmds = Vec::with_capacity(n);
+        for _ in 0..n {
+            cmds.push(match r.read_u8()? {
+                OP_MOVE => Cmd::MoveTo(point(&mut r)?),
+                OP_LINE => Cmd::LineTo(point(&mut r)?),
+                OP_CURVE => Cmd::CurveTo(point(&mut r)?, point(&mut r)?, point(&mut r)?),
+                OP_QUAD => Cmd::QuadTo(point(&mut r)?, point(&mut r)?),
+                OP_CLOSE => Cmd::Close,
+                op => return Err(format!("unknown path command: {}", op).into()),
+            });
+        }
+        contours.push(Contour {
+            path: Path::new(cmds),
+            kind: if flags & KIND_INDEX != 0 {
+                Kind::Index
+            } else {
+                Kind::Intermediate
+            },
+            elevation,
+        });
+    }
+    Ok(contours)
+}
+
+fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+fn unzigzag(v: u64) -> i64 {
+    (v >> 1) as i64 ^ -((v & 1) as i64)
+}
+
+fn write_varint<W: Write>(w: &mut W, mut v: u64) -> io::Result<()> {
+    while v >= 0x80 {
+        w.write_u8((v as u8) | 0x80)?;
+        v >>= 7;
+    }
+    w.write_u8(v as u8)
+}
+
+fn read_varint(r: &mut &[u8]) -> io::Result<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = r.read_u8()?;
+        v |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint too long",
+    ))
+}
+
+pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let data = extract::Data::open(&args.src)?;
+    let mut w = io::BufWriter::new(fs::File::create(&args.dst)?);
+    write(&data, &mut w, args.compression, args.precision)?;
+    w.flush()?;
+    println!(
+        "{} → {} bytes",
+        fs::metadata(&args.src)?.len(),
+        fs::metadata(&args.dst)?.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> extract::Data {
+        let p = Point::from_xy;
+        let contour = |cmds, kind, elevation| Contour {
+            path: Path::new(cmds),
+            kind,
+            elevation,
+        };
+        extract::Data::new(
+            Size::new(400.0, 300.0),
+            "35°38′47″N 080°03′00″W-35°29′20″N 079°51′00″W"
+                .parse()
+                .unwrap(),
+            geo::Projection::Equirectangular,
+            None,
+            vec![
+                contour(
+                    vec![
+                        Cmd::MoveTo(p(10.0, 20.5)),
+                        Cmd::LineTo(p(30.25, 20.5)),
+                        Cmd::CurveTo(p(40.0, 10.0), p(50.0, 30.0), p(60.0, 20.0)),
+                        Cmd::QuadTo(p(70.0, 0.0), p(80.0, 20.0)),
+                        Cmd::Close,
+                    ],
+                    Kind::Index,
+                    Some(1200.0),
+                ),
+                contour(
+                    vec![Cmd::MoveTo(p(0.0, 299.99)), Cmd::LineTo(p(399.99, 0.0))],
+                    Kind::Intermediate,
+                    None,
+                ),
+            ],
+        )
+    }
+
+    fn pack(compression: Compression) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write(&data(), &mut buf, compression, 0.01).unwrap();
+        buf
+    }
+
+    fn assert_same(a: &extract::Data, b: &extract::Data) {
+        assert_eq!(a.size.to_string(), b.size.to_string());
+        assert_eq!(a.region.to_string(), b.region.to_string());
+        assert_eq!(a.contours.len(), b.contours.len());
+        for (a, b) in a.contours.iter().zip(&b.contours) {
+            assert_eq!(a.kind, b.kind);
+            assert_eq!(a.elevation, b.elevation);
+            assert_eq!(a.path.cmds().len(), b.path.cmds().len());
+            for (a, b) in a.path.cmds().iter().zip(b.path.cmds()) {
+                assert_eq!(std::mem::discriminant(a), std::mem::discriminant(b));
+                for (p, q) in a.points().zip(b.points()) {
+                    assert!(p.distance_to(q) < 1e-9, "{:?} != {:?}", p, q);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut compressions = vec![Compression::None, Compression::Deflate];
+        if cfg!(feature = "zstd") {
+            compressions.push(Compression::Zstd);
+        }
+        for compression in compressions {
+            assert_same(&data(), &read(&pack(compression)).unwrap());
+        }
+    }
+
+    #[test]
+    fn opens_packed_files() {
+        let src = std::env::temp_dir().join(format!("topo-pack-{}.topo", std::process::id()));
+        fs::write(&src, pack(Compression::Deflate)).unwrap();
+        let opened = extract::Data::open(&src);
+        fs::remove_file(&src).unwrap();
+        assert_same(&data(), &opened.unwrap());
+    }
+
+    #[test]
+    fn rejects_a_body_longer_than_its_header_says() {
+        for compression in [Compression::None, Compression::Deflate] {
+            let packed = pack(compression);
+            let len = u32::from_be_bytes(packed[6..10].try_into().unwrap()) as usize;
+            let mut header: serde_json::Value =
+                serde_json::from_slice(&packed[10..10 + len]).unwrap();
+            header["body"] = serde_json::json!(header["body"].as_u64().unwrap() - 1);
+            let header = serde_json::to_vec(&header).unwrap();
+
+            let mut forged = packed[..6].to_vec();
+            forged.extend((header.len() as u32).to_be_bytes());
+            forged.extend(header);
+            forged.extend(&packed[10 + len..]);
+            assert!(read(&forged).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_coordinates_that_overflow() {
+        let mut body = vec![KIND_INTERMEDIATE];
+        write_varint(&mut body, 2).unwrap();
+        for _ in 0..2 {
+            body.push(OP_LINE);
+            write_varint(&mut body, zigzag(i64::MAX)).unwrap();
+            write_varint(&mut body, 0).unwrap();
+        }
+        let header = Header {
+            size: Size::new(1.0, 1.0),
+            region: "35°38′47″N 080°03′00″W-35°29′20″N 079°51′00″W"
+                .parse()
+                .unwrap(),
+            projection: geo::Projection::Equirectangular,
+            fit: None,
+            precision: 1.0,
+            contours: 1,
+            body: body.len(),
+        };
+        assert!(decode(&body, &header).is_err());
+    }
+}
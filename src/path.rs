@@ -5,18 +5,63 @@ use serde::{de, ser};
 
 use crate::{Point, Rect};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Path {
     cmds: Vec<Cmd>,
 }
 
 impl Path {
+    pub fn new(cmds: Vec<Cmd>) -> Self {
+        Self { cmds }
+    }
+
+    // A polyline through the points, closed when asked to be, in which case
+    // a last point repeating the first is left out.
+    pub fn from_points(points: &[Point], closed: bool) -> Self {
+        let points = match points {
+            [first, rest @ .., last] if closed && first == last => &points[..rest.len() + 1],
+            _ => points,
+        };
+        let mut cmds = points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                if i == 0 {
+                    Cmd::MoveTo(*p)
+                } else {
+                    Cmd::LineTo(*p)
+                }
+            })
+            .collect::<Vec<_>>();
+        if closed {
+            cmds.push(Cmd::Close);
+        }
+        Self { cmds }
+    }
+
     pub fn draw(&self, ctx: &Context) {
+        let mut cur = Point::from_xy(0.0, 0.0);
+        let mut start = cur;
         for cmd in self.cmds.iter() {
             match cmd {
-                Cmd::MoveTo(p) => ctx.move_to(p.x(), p.y()),
+                Cmd::MoveTo(p) => {
+                    ctx.move_to(p.x(), p.y());
+                    start = *p;
+                }
                 Cmd::LineTo(p) => ctx.line_to(p.x(), p.y()),
+                Cmd::CurveTo(c1, c2, p) => {
+                    ctx.curve_to(c1.x(), c1.y(), c2.x(), c2.y(), p.x(), p.y())
+                }
+                Cmd::QuadTo(c, p) => {
+                    let (c1, c2) = quad_to_cubic(&cur, c, p);
+                    ctx.curve_to(c1.x(), c1.y(), c2.x(), c2.y(), p.x(), p.y());
+                }
+                Cmd::Close => ctx.close_path(),
             }
+            cur = match cmd.point() {
+                Some(p) => *p,
+                None => start,
+            };
         }
     }
 
@@ -25,10 +70,7 @@ impl Path {
         F: Fn(&Point) -> Point,
     {
         for cmd in self.cmds.iter_mut() {
-            match cmd {
-                Cmd::MoveTo(p) => *p = tx(p),
-                Cmd::LineTo(p) => *p = tx(p),
-            }
+            *cmd = cmd.map(&tx);
         }
     }
 
@@ -37,14 +79,53 @@ impl Path {
         F: Fn(&Point) -> Point,
     {
         Path {
-            cmds: self
-                .cmds
-                .iter()
-                .map(|cmd| match cmd {
-                    Cmd::MoveTo(p) => Cmd::MoveTo(tx(p)),
-                    Cmd::LineTo(p) => Cmd::LineTo(tx(p)),
-                })
-                .collect(),
+            cmds: self.cmds.iter().map(|cmd| cmd.map(&tx)).collect(),
+        }
+    }
+
+    // Drops the segments whose control points all fall outside of rect,
+    // starting a new subpath wherever segments were dropped. What remains
+    // draws the same as the whole path within rect.
+    pub fn clip(&self, rect: &Rect) -> Option<Path> {
+        let mut cmds = Vec::new();
+        let mut cur = Point::from_xy(0.0, 0.0);
+        let mut start = cur;
+        let mut pen: Option<Point> = None;
+        let mut whole = true;
+        for cmd in self.cmds.iter() {
+            let (to, bounds) = match cmd {
+                Cmd::MoveTo(p) => {
+                    (cur, start, pen, whole) = (*p, *p, None, true);
+                    continue;
+                }
+                Cmd::Close => (start, Rect::from_points(&cur, &start)),
+                _ => {
+                    let to = *cmd.point().unwrap();
+                    let bounds = cmd.points().fold(Rect::from_points(&cur, &to), |r, p| {
+                        r.union(&Rect::from_points(p, p))
+                    });
+                    (to, bounds)
+                }
+            };
+
+            if rect.intersects(&bounds) {
+                if pen != Some(cur) {
+                    cmds.push(Cmd::MoveTo(cur));
+                }
+                cmds.push(match cmd {
+                    Cmd::Close if !whole => Cmd::LineTo(start),
+                    _ => *cmd,
+                });
+                pen = Some(to);
+            } else {
+                whole = false;
+            }
+            cur = to;
+        }
+        if cmds.is_empty() {
+            None
+        } else {
+            Some(Path { cmds })
         }
     }
 
@@ -56,13 +137,16 @@ impl Path {
         self.cmds.is_empty()
     }
 
+    pub fn cmds(&self) -> &[Cmd] {
+        &self.cmds
+    }
+
     pub fn bounds(&self) -> Rect {
         let mut min_x = f64::INFINITY;
         let mut min_y = f64::INFINITY;
         let mut max_x = f64::NEG_INFINITY;
         let mut max_y = f64::NEG_INFINITY;
-        for cmd in self.cmds.iter() {
-            let p = cmd.point();
+        for p in self.cmds.iter().flat_map(|cmd| cmd.points()) {
             min_x = min_x.min(p.x());
             min_y = min_y.min(p.y());
             max_x = max_x.max(p.x());
@@ -71,25 +155,283 @@ impl Path {
         Rect::new(Point::from_xy(min_x, min_y), Point::from_xy(max_x, max_y))
     }
 
-    pub fn is_valid(s: &str) -> bool {
-        let mut elems = s.split_whitespace();
-        while let Some(cmd) = elems.next() {
+    // Approximates the path with one polyline per subpath, splitting each
+    // curve into a fixed number of line segments.
+    pub fn flatten(&self) -> Vec<Vec<Point>> {
+        const STEPS: usize = 8;
+        let mut lines: Vec<Vec<Point>> = Vec::new();
+        let mut cur = Point::from_xy(0.0, 0.0);
+        for cmd in self.cmds.iter() {
             match cmd {
-                "M" | "L" => {
-                    if !has_a::<f64>(&mut elems) || !has_a::<f64>(&mut elems) {
-                        return false;
+                Cmd::MoveTo(p) => lines.push(vec![*p]),
+                Cmd::LineTo(p) => push_point(&mut lines, &cur, *p),
+                Cmd::CurveTo(c1, c2, p) => {
+                    for i in 1..=STEPS {
+                        let t = i as f64 / STEPS as f64;
+                        push_point(&mut lines, &cur, cubic_at(&cur, c1, c2, p, t));
                     }
                 }
+                Cmd::QuadTo(c, p) => {
+                    let (c1, c2) = quad_to_cubic(&cur, c, p);
+                    for i in 1..=STEPS {
+                        let t = i as f64 / STEPS as f64;
+                        push_point(&mut lines, &cur, cubic_at(&cur, &c1, &c2, p, t));
+                    }
+                }
+                Cmd::Close => {
+                    if let Some(line) = lines.last_mut() {
+                        if line.len() > 1 && line.first() != line.last() {
+                            line.push(line[0]);
+                        }
+                    }
+                }
+            }
+            cur = match (cmd.point(), lines.last()) {
+                (Some(p), _) => *p,
+                (None, Some(line)) => line[0],
+                (None, None) => cur,
+            };
+        }
+        lines
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(self.cmds.last(), Some(Cmd::Close))
+            || match (self.cmds.first(), self.cmds.last()) {
+                (Some(a), Some(b)) => self.cmds.len() > 2 && a.point() == b.point(),
+                _ => false,
+            }
+    }
+
+    pub fn is_valid(s: &str) -> bool {
+        Path::from_str(s).is_ok()
+    }
+}
+
+fn push_point(lines: &mut Vec<Vec<Point>>, cur: &Point, p: Point) {
+    match lines.last_mut() {
+        Some(line) => line.push(p),
+        None => lines.push(vec![*cur, p]),
+    }
+}
+
+fn cubic_at(p0: &Point, c1: &Point, c2: &Point, p: &Point, t: f64) -> Point {
+    let mt = 1.0 - t;
+    let (a, b, c, d) = (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+    Point::from_xy(
+        a * p0.x() + b * c1.x() + c * c2.x() + d * p.x(),
+        a * p0.y() + b * c1.y() + c * c2.y() + d * p.y(),
+    )
+}
+
+fn quad_to_cubic(p0: &Point, c: &Point, p: &Point) -> (Point, Point) {
+    (
+        Point::from_xy(
+            p0.x() + 2.0 / 3.0 * (c.x() - p0.x()),
+            p0.y() + 2.0 / 3.0 * (c.y() - p0.y()),
+        ),
+        Point::from_xy(
+            p.x() + 2.0 / 3.0 * (c.x() - p.x()),
+            p.y() + 2.0 / 3.0 * (c.y() - p.y()),
+        ),
+    )
+}
+
+fn reflect(c: &Point, about: &Point) -> Point {
+    Point::from_xy(2.0 * about.x() - c.x(), 2.0 * about.y() - c.y())
+}
+
+struct Lexer<'a> {
+    s: &'a [u8],
+    ix: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(s: &'a str) -> Self {
+        Self {
+            s: s.as_bytes(),
+            ix: 0,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self.ix < self.s.len()
+            && (self.s[self.ix].is_ascii_whitespace() || self.s[self.ix] == b',')
+        {
+            self.ix += 1;
+        }
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        match self.s.get(self.ix) {
+            Some(&c) if c.is_ascii_alphabetic() && c != b'e' && c != b'E' => {
+                self.ix += 1;
+                Some(c)
+            }
+            _ => None,
+        }
+    }
+
+    fn at_number(&mut self) -> bool {
+        self.skip_separators();
+        matches!(self.s.get(self.ix), Some(c) if c.is_ascii_digit() || matches!(c, b'-' | b'+' | b'.'))
+    }
+
+    fn is_done(&mut self) -> bool {
+        self.skip_separators();
+        self.ix >= self.s.len()
+    }
+
+    fn number(&mut self) -> Result<f64, Box<dyn Error>> {
+        self.skip_separators();
+        let start = self.ix;
+        let digits = |lx: &mut Self| {
+            let from = lx.ix;
+            while lx.ix < lx.s.len() && lx.s[lx.ix].is_ascii_digit() {
+                lx.ix += 1;
+            }
+            lx.ix - from
+        };
+
+        if matches!(self.s.get(self.ix), Some(b'-' | b'+')) {
+            self.ix += 1;
+        }
+        let mut n = digits(self);
+        if self.s.get(self.ix) == Some(&b'.') {
+            self.ix += 1;
+            n += digits(self);
+        }
+        if n == 0 {
+            return Err(format!("expected number at offset {}", start).into());
+        }
+        if matches!(self.s.get(self.ix), Some(b'e' | b'E')) {
+            let mark = self.ix;
+            self.ix += 1;
+            if matches!(self.s.get(self.ix), Some(b'-' | b'+')) {
+                self.ix += 1;
+            }
+            if digits(self) == 0 {
+                self.ix = mark;
+            }
+        }
+
+        Ok(std::str::from_utf8(&self.s[start..self.ix])?.parse()?)
+    }
 
-                _ => return false,
+    fn flag(&mut self) -> Result<bool, Box<dyn Error>> {
+        self.skip_separators();
+        match self.s.get(self.ix) {
+            Some(b'0') => {
+                self.ix += 1;
+                Ok(false)
             }
+            Some(b'1') => {
+                self.ix += 1;
+                Ok(true)
+            }
+            _ => Err(format!("expected flag at offset {}", self.ix).into()),
         }
-        true
+    }
+
+    fn point(&mut self, rel: bool, cur: &Point) -> Result<Point, Box<dyn Error>> {
+        let x = self.number()?;
+        let y = self.number()?;
+        Ok(if rel {
+            Point::from_xy(cur.x() + x, cur.y() + y)
+        } else {
+            Point::from_xy(x, y)
+        })
     }
 }
 
-fn has_a<'a, T: FromStr>(elems: &mut impl Iterator<Item = &'a str>) -> bool {
-    elems.next().and_then(|s| s.parse::<T>().ok()).is_some()
+// Converts an SVG endpoint-parameterized elliptical arc into cubic bézier
+// segments of at most 90° each (SVG 1.1 implementation notes, F.6.5).
+#[allow(clippy::too_many_arguments)]
+fn arc_to_cubics(
+    cmds: &mut Vec<Cmd>,
+    p0: &Point,
+    rx: f64,
+    ry: f64,
+    angle: f64,
+    large_arc: bool,
+    sweep: bool,
+    p: &Point,
+) {
+    if p0 == p {
+        return;
+    }
+
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    if rx == 0.0 || ry == 0.0 {
+        cmds.push(Cmd::LineTo(*p));
+        return;
+    }
+
+    let (sin_phi, cos_phi) = angle.to_radians().sin_cos();
+    let dx = (p0.x() - p.x()) / 2.0;
+    let dy = (p0.y() - p.y()) / 2.0;
+    let x1 = cos_phi * dx + sin_phi * dy;
+    let y1 = -sin_phi * dx + cos_phi * dy;
+
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut k = (num / den).max(0.0).sqrt();
+    if large_arc == sweep {
+        k = -k;
+    }
+    let cx1 = k * rx * y1 / ry;
+    let cy1 = -k * ry * x1 / rx;
+    let cx = cos_phi * cx1 - sin_phi * cy1 + (p0.x() + p.x()) / 2.0;
+    let cy = sin_phi * cx1 + cos_phi * cy1 + (p0.y() + p.y()) / 2.0;
+
+    let angle_between =
+        |ux: f64, uy: f64, vx: f64, vy: f64| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+    let ux = (x1 - cx1) / rx;
+    let uy = (y1 - cy1) / ry;
+    let vx = (-x1 - cx1) / rx;
+    let vy = (-y1 - cy1) / ry;
+    let theta = angle_between(1.0, 0.0, ux, uy);
+    let mut delta = angle_between(ux, uy, vx, vy);
+    if !sweep && delta > 0.0 {
+        delta -= 2.0 * std::f64::consts::PI;
+    } else if sweep && delta < 0.0 {
+        delta += 2.0 * std::f64::consts::PI;
+    }
+
+    let n = (delta.abs() / std::f64::consts::FRAC_PI_2).ceil().max(1.0) as usize;
+    let step = delta / n as f64;
+    let t = 4.0 / 3.0 * (step / 4.0).tan();
+    let at = |a: f64| {
+        let (sin_a, cos_a) = a.sin_cos();
+        let (x, y) = (rx * cos_a, ry * sin_a);
+        let (dx, dy) = (-rx * sin_a, ry * cos_a);
+        (
+            Point::from_xy(
+                cx + cos_phi * x - sin_phi * y,
+                cy + sin_phi * x + cos_phi * y,
+            ),
+            (cos_phi * dx - sin_phi * dy, sin_phi * dx + cos_phi * dy),
+        )
+    };
+    for i in 0..n {
+        let a0 = theta + step * i as f64;
+        let a1 = a0 + step;
+        let (q0, d0) = at(a0);
+        let (q1, d1) = at(a1);
+        let q1 = if i == n - 1 { *p } else { q1 };
+        cmds.push(Cmd::CurveTo(
+            Point::from_xy(q0.x() + t * d0.0, q0.y() + t * d0.1),
+            Point::from_xy(q1.x() - t * d1.0, q1.y() - t * d1.1),
+            q1,
+        ));
+    }
 }
 
 impl FromStr for Path {
@@ -97,17 +439,105 @@ impl FromStr for Path {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut cmds = Vec::new();
-        let mut elems = s.split_whitespace();
-        while let Some(cmd) = elems.next() {
-            let pt = Point::from_xy(
-                elems.next().ok_or("no x")?.parse()?,
-                elems.next().ok_or("no y")?.parse()?,
-            );
-            cmds.push(match cmd {
-                "M" => Cmd::MoveTo(pt),
-                "L" => Cmd::LineTo(pt),
-                _ => return Err(format!("unknown command: {}", cmd).into()),
-            });
+        let mut lx = Lexer::new(s);
+        let mut cur = Point::from_xy(0.0, 0.0);
+        let mut start = cur;
+        let mut last_cubic: Option<Point> = None;
+        let mut last_quad: Option<Point> = None;
+        let mut prev: Option<u8> = None;
+
+        while !lx.is_done() {
+            let cmd = match lx.command() {
+                Some(c) => c,
+                // Repeated arguments imply the previous command, except that
+                // coordinates following a moveto are implicit linetos.
+                None => match prev {
+                    Some(b'M') => b'L',
+                    Some(b'm') => b'l',
+                    Some(c) if c != b'Z' && c != b'z' => c,
+                    _ => return Err(format!("expected command at offset {}", lx.ix).into()),
+                },
+            };
+            if prev.is_none() && cmd != b'M' && cmd != b'm' {
+                return Err(format!("path must begin with moveto: {}", s).into());
+            }
+
+            let rel = cmd.is_ascii_lowercase();
+            let (mut cubic, mut quad) = (None, None);
+            match cmd.to_ascii_uppercase() {
+                b'M' => {
+                    cur = lx.point(rel, &cur)?;
+                    start = cur;
+                    cmds.push(Cmd::MoveTo(cur));
+                }
+                b'L' => {
+                    cur = lx.point(rel, &cur)?;
+                    cmds.push(Cmd::LineTo(cur));
+                }
+                b'H' => {
+                    let x = lx.number()?;
+                    cur = Point::from_xy(if rel { cur.x() + x } else { x }, cur.y());
+                    cmds.push(Cmd::LineTo(cur));
+                }
+                b'V' => {
+                    let y = lx.number()?;
+                    cur = Point::from_xy(cur.x(), if rel { cur.y() + y } else { y });
+                    cmds.push(Cmd::LineTo(cur));
+                }
+                b'C' => {
+                    let c1 = lx.point(rel, &cur)?;
+                    let c2 = lx.point(rel, &cur)?;
+                    let p = lx.point(rel, &cur)?;
+                    cmds.push(Cmd::CurveTo(c1, c2, p));
+                    cubic = Some(c2);
+                    cur = p;
+                }
+                b'S' => {
+                    let c1 = last_cubic.map_or(cur, |c| reflect(&c, &cur));
+                    let c2 = lx.point(rel, &cur)?;
+                    let p = lx.point(rel, &cur)?;
+                    cmds.push(Cmd::CurveTo(c1, c2, p));
+                    cubic = Some(c2);
+                    cur = p;
+                }
+                b'Q' => {
+                    let c = lx.point(rel, &cur)?;
+                    let p = lx.point(rel, &cur)?;
+                    cmds.push(Cmd::QuadTo(c, p));
+                    quad = Some(c);
+                    cur = p;
+                }
+                b'T' => {
+                    let c = last_quad.map_or(cur, |c| reflect(&c, &cur));
+                    let p = lx.point(rel, &cur)?;
+                    cmds.push(Cmd::QuadTo(c, p));
+                    quad = Some(c);
+                    cur = p;
+                }
+                b'A' => {
+                    let rx = lx.number()?;
+                    let ry = lx.number()?;
+                    let angle = lx.number()?;
+                    let large_arc = lx.flag()?;
+                    let sweep = lx.flag()?;
+                    let p = lx.point(rel, &cur)?;
+                    arc_to_cubics(&mut cmds, &cur, rx, ry, angle, large_arc, sweep, &p);
+                    cur = p;
+                }
+                b'Z' => {
+                    cmds.push(Cmd::Close);
+                    cur = start;
+                }
+                _ => return Err(format!("unknown command: {}", cmd as char).into()),
+            }
+
+            last_cubic = cubic;
+            last_quad = quad;
+            prev = Some(cmd);
+
+            if matches!(cmd, b'Z' | b'z') && lx.at_number() {
+                return Err(format!("unexpected number at offset {}", lx.ix).into());
+            }
         }
         Ok(Path { cmds })
     }
@@ -161,17 +591,47 @@ impl<'d> de::Visitor<'d> for PathVisitor {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Cmd {
     MoveTo(Point),
     LineTo(Point),
+    CurveTo(Point, Point, Point),
+    QuadTo(Point, Point),
+    Close,
 }
 
 impl Cmd {
-    pub fn point(&self) -> &Point {
+    pub fn point(&self) -> Option<&Point> {
         match self {
-            Cmd::MoveTo(p) => p,
-            Cmd::LineTo(p) => p,
+            Cmd::MoveTo(p) => Some(p),
+            Cmd::LineTo(p) => Some(p),
+            Cmd::CurveTo(_, _, p) => Some(p),
+            Cmd::QuadTo(_, p) => Some(p),
+            Cmd::Close => None,
+        }
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = &Point> {
+        match self {
+            Cmd::MoveTo(p) | Cmd::LineTo(p) => [Some(p), None, None],
+            Cmd::CurveTo(c1, c2, p) => [Some(c1), Some(c2), Some(p)],
+            Cmd::QuadTo(c, p) => [Some(c), Some(p), None],
+            Cmd::Close => [None, None, None],
+        }
+        .into_iter()
+        .flatten()
+    }
+
+    fn map<F>(&self, tx: F) -> Cmd
+    where
+        F: Fn(&Point) -> Point,
+    {
+        match self {
+            Cmd::MoveTo(p) => Cmd::MoveTo(tx(p)),
+            Cmd::LineTo(p) => Cmd::LineTo(tx(p)),
+            Cmd::CurveTo(c1, c2, p) => Cmd::CurveTo(tx(c1), tx(c2), tx(p)),
+            Cmd::QuadTo(c, p) => Cmd::QuadTo(tx(c), tx(p)),
+            Cmd::Close => Cmd::Close,
         }
     }
 }
@@ -181,6 +641,149 @@ impl fmt::Display for Cmd {
         match self {
             Cmd::MoveTo(p) => write!(f, "M {} {}", p.x(), p.y()),
             Cmd::LineTo(p) => write!(f, "L {} {}", p.x(), p.y()),
+            Cmd::CurveTo(c1, c2, p) => write!(
+                f,
+                "C {} {} {} {} {} {}",
+                c1.x(),
+                c1.y(),
+                c2.x(),
+                c2.y(),
+                p.x(),
+                p.y()
+            ),
+            Cmd::QuadTo(c, p) => write!(f, "Q {} {} {} {}", c.x(), c.y(), p.x(), p.y()),
+            Cmd::Close => write!(f, "Z"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(x: f64, y: f64) -> Point {
+        Point::from_xy(x, y)
+    }
+
+    fn parse(s: &str) -> Vec<Cmd> {
+        s.parse::<Path>().unwrap().cmds
+    }
+
+    #[test]
+    fn parses_each_command() {
+        assert_eq!(
+            parse("M1 2 L3 4 H5 V6 C7 8 9 10 11 12 S13 14 15 16 Q17 18 19 20 T21 22 Z"),
+            vec![
+                Cmd::MoveTo(p(1.0, 2.0)),
+                Cmd::LineTo(p(3.0, 4.0)),
+                Cmd::LineTo(p(5.0, 4.0)),
+                Cmd::LineTo(p(5.0, 6.0)),
+                Cmd::CurveTo(p(7.0, 8.0), p(9.0, 10.0), p(11.0, 12.0)),
+                Cmd::CurveTo(p(13.0, 14.0), p(13.0, 14.0), p(15.0, 16.0)),
+                Cmd::QuadTo(p(17.0, 18.0), p(19.0, 20.0)),
+                Cmd::QuadTo(p(21.0, 22.0), p(21.0, 22.0)),
+                Cmd::Close,
+            ]
+        );
+    }
+
+    #[test]
+    fn relative_commands_are_offsets_from_the_current_point() {
+        assert_eq!(
+            parse("m1 2 l3 4 h5 v6 c1 1 2 2 3 3 z m1 1 q1 0 2 2"),
+            vec![
+                Cmd::MoveTo(p(1.0, 2.0)),
+                Cmd::LineTo(p(4.0, 6.0)),
+                Cmd::LineTo(p(9.0, 6.0)),
+                Cmd::LineTo(p(9.0, 12.0)),
+                Cmd::CurveTo(p(10.0, 13.0), p(11.0, 14.0), p(12.0, 15.0)),
+                Cmd::Close,
+                Cmd::MoveTo(p(2.0, 3.0)),
+                Cmd::QuadTo(p(3.0, 3.0), p(4.0, 5.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn smooth_curves_reflect_the_previous_control_point() {
+        assert_eq!(
+            parse("M0 0 C0 1 2 1 2 0 S4 -1 4 0")[2],
+            Cmd::CurveTo(p(2.0, -1.0), p(4.0, -1.0), p(4.0, 0.0))
+        );
+        assert_eq!(
+            parse("M0 0 Q1 1 2 0 T4 0")[2],
+            Cmd::QuadTo(p(3.0, -1.0), p(4.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn repeated_arguments_repeat_the_command() {
+        assert_eq!(
+            parse("M0 0 1 1 2 2"),
+            vec![
+                Cmd::MoveTo(p(0.0, 0.0)),
+                Cmd::LineTo(p(1.0, 1.0)),
+                Cmd::LineTo(p(2.0, 2.0)),
+            ]
+        );
+        assert_eq!(
+            parse("m1 1 1 1 h1 2"),
+            vec![
+                Cmd::MoveTo(p(1.0, 1.0)),
+                Cmd::LineTo(p(2.0, 2.0)),
+                Cmd::LineTo(p(3.0, 2.0)),
+                Cmd::LineTo(p(5.0, 2.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_compact_numbers() {
+        assert_eq!(parse("M1.5.5"), vec![Cmd::MoveTo(p(1.5, 0.5))]);
+        assert_eq!(parse("M1e-3-2E+1"), vec![Cmd::MoveTo(p(0.001, -20.0))]);
+        assert_eq!(parse("M-.5,+.5"), vec![Cmd::MoveTo(p(-0.5, 0.5))]);
+    }
+
+    #[test]
+    fn arcs_become_curves_ending_at_the_endpoint() {
+        let cmds = parse("M0 0 A10 10 0 0 1 20 0");
+        assert_eq!(cmds.len(), 3);
+        assert_eq!(cmds[2].point(), Some(&p(20.0, 0.0)));
+        // The sweep flag picks which side of the chord the arc bulges to.
+        let bulge = |s: &str| parse(s)[1].points().next().unwrap().y();
+        assert!(bulge("M0 0 A10 10 0 0 1 20 0") < 0.0);
+        assert!(bulge("M0 0 A10 10 0 0 0 20 0") > 0.0);
+    }
+
+    #[test]
+    fn degenerate_arcs() {
+        // A zero radius draws a straight line and identical endpoints draw
+        // nothing at all.
+        assert_eq!(
+            parse("M0 0 A0 5 0 0 1 10 0"),
+            vec![Cmd::MoveTo(p(0.0, 0.0)), Cmd::LineTo(p(10.0, 0.0))]
+        );
+        assert_eq!(parse("M3 4 A5 5 0 1 1 3 4"), vec![Cmd::MoveTo(p(3.0, 4.0))]);
+    }
+
+    #[test]
+    fn rejects_malformed_paths() {
+        for s in [
+            "L1 1",
+            "M1",
+            "M1 1 X2 2",
+            "M0 0 A1 1 0 2 0 1 1",
+            "M0 0 Z 1",
+            "M.",
+        ] {
+            assert!(s.parse::<Path>().is_err(), "{}", s);
         }
     }
+
+    #[test]
+    fn display_round_trips() {
+        let s = "M0 0 L1 2 C3 4 5 6 7 8 Q9 10 11 12 Z";
+        let path = s.parse::<Path>().unwrap();
+        assert_eq!(path.to_string().parse::<Path>().unwrap().cmds, path.cmds);
+    }
 }
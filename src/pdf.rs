@@ -0,0 +1,514 @@
+use std::{error::Error, path};
+
+use lopdf::{content::Content, Dictionary, Document, Object};
+
+use crate::{
+    elevation::Label,
+    extract,
+    matcher::{self, Match},
+    Cmd, Color, Matcher, Path, Point, Size, Transform,
+};
+
+// The subset of the PDF graphics state that contour matching and reading
+// labels depend on.
+#[derive(Debug, Clone)]
+struct State {
+    ctm: Transform,
+    stroke: Option<Color>,
+    fill: Option<Color>,
+    line_width: f64,
+    font: Option<Vec<u8>>,
+    leading: f64,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            ctm: Transform::identity(),
+            stroke: Some(Color::from_rgb(0, 0, 0)),
+            fill: Some(Color::from_rgb(0, 0, 0)),
+            line_width: 1.0,
+            font: None,
+            leading: 0.0,
+        }
+    }
+}
+
+impl State {
+    // Line widths are compared in page units, which is what pdftocairo
+    // writes into the SVG.
+    fn style(&self, stroked: bool, filled: bool, layers: &[String]) -> matcher::Style {
+        let [a, b, c, d, _, _] = self.ctm.coefficients();
+        matcher::Style {
+            stroke: if stroked { self.stroke } else { None },
+            stroke_width: if stroked {
+                Some(self.line_width * (a * d - b * c).abs().sqrt())
+            } else {
+                None
+            },
+            fill: Some(match (filled, self.fill) {
+                (true, Some(c)) => c.to_string(),
+                _ => String::from("none"),
+            }),
+            groups: layers.to_vec(),
+            ..Default::default()
+        }
+    }
+}
+
+struct Walker<'a> {
+    doc: &'a Document,
+    matcher: &'a Matcher,
+    page: Transform,
+    paths: Vec<Match>,
+    labels: Vec<Label>,
+    layers: Vec<String>,
+    marks: Vec<bool>,
+    depth: usize,
+}
+
+const MAX_FORM_DEPTH: usize = 32;
+
+// How far up the page tree an inherited attribute is looked for, so that a
+// tree that loops back on itself can't be walked forever.
+const MAX_TREE_DEPTH: usize = 32;
+
+// Reals are stored as f32, so widen them through their shortest decimal form
+// to get back the value that was written in the file (0.99001, not
+// 0.9900100231170654).
+fn number(obj: &Object) -> Result<f64, Box<dyn Error>> {
+    match obj {
+        Object::Real(v) => Ok(v.to_string().parse()?),
+        _ => Ok(obj.as_float()? as f64),
+    }
+}
+
+fn numbers(ops: &[Object]) -> Result<Vec<f64>, Box<dyn Error>> {
+    ops.iter().map(number).collect()
+}
+
+fn color_from(ops: &[f64]) -> Option<Color> {
+    let to_u8 = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    match *ops {
+        [g] => Some(Color::from_rgb(to_u8(g), to_u8(g), to_u8(g))),
+        [r, g, b] => Some(Color::from_rgb(to_u8(r), to_u8(g), to_u8(b))),
+        [c, m, y, k] => Some(Color::from_rgb(
+            to_u8((1.0 - c) * (1.0 - k)),
+            to_u8((1.0 - m) * (1.0 - k)),
+            to_u8((1.0 - y) * (1.0 - k)),
+        )),
+        _ => None,
+    }
+}
+
+impl<'a> Walker<'a> {
+    fn deref(&self, obj: &'a Object) -> Result<&'a Object, Box<dyn Error>> {
+        Ok(self.doc.dereference(obj)?.1)
+    }
+
+    fn resource(
+        &self,
+        resources: Option<&'a Dictionary>,
+        category: &[u8],
+        name: &[u8],
+    ) -> Option<&'a Object> {
+        let dict = self
+            .deref(resources?.get(category).ok()?)
+            .ok()?
+            .as_dict()
+            .ok()?;
+        self.deref(dict.get(name).ok()?).ok()
+    }
+
+    fn layer_name(&self, resources: Option<&'a Dictionary>, ops: &[Object]) -> Option<String> {
+        if ops.first()?.as_name().ok()? != b"OC" {
+            return None;
+        }
+        let props = match ops.get(1)? {
+            Object::Name(name) => self.resource(resources, b"Properties", name)?,
+            obj => self.deref(obj).ok()?,
+        };
+        lopdf::decode_text_string(props.as_dict().ok()?.get(b"Name").ok()?).ok()
+    }
+
+    // The text in a string shown with the named font, falling back to the
+    // bytes themselves when the font's encoding can't be worked out.
+    fn decode(&self, resources: Option<&'a Dictionary>, font: Option<&[u8]>, s: &[u8]) -> String {
+        font.and_then(|f| self.resource(resources, b"Font", f))
+            .and_then(|f| f.as_dict().ok())
+            .and_then(|f| f.get_font_encoding(self.doc).ok())
+            .and_then(|e| e.bytes_to_string(s).ok())
+            .unwrap_or_else(|| String::from_utf8_lossy(s).into_owned())
+    }
+
+    // Labels are runs of text shown without moving to a new position, placed
+    // where the run starts.
+    fn end_text(&mut self, text: &mut Option<(Point, String)>) {
+        if let Some((at, s)) = text.take() {
+            self.labels.extend(Label::parse(at, &s));
+        }
+    }
+
+    fn walk(
+        &mut self,
+        content: &[u8],
+        resources: Option<&'a Dictionary>,
+        state: State,
+    ) -> Result<(), Box<dyn Error>> {
+        let content = Content::decode(content)?;
+        let mut stack = Vec::new();
+        let mut state = state;
+        let mut cmds: Vec<Cmd> = Vec::new();
+        let mut cur = Point::from_xy(0.0, 0.0);
+        // The text and text line matrices, and the run of text being shown.
+        let mut tm = Transform::identity();
+        let mut tlm = Transform::identity();
+        let mut text: Option<(Point, String)> = None;
+
+        for op in content.operations.iter() {
+            let args = &op.operands;
+            match op.operator.as_str() {
+                "q" => stack.push(state.clone()),
+                "Q" => state = stack.pop().unwrap_or_default(),
+                "cm" => {
+                    if let [a, b, c, d, e, f] = numbers(args)?[..] {
+                        state.ctm = Transform::new(a, b, c, d, e, f).then(&state.ctm);
+                    }
+                }
+                "w" => state.line_width = number(args.first().ok_or("no width")?)?,
+                "gs" => {
+                    let gs = args
+                        .first()
+                        .and_then(|n| n.as_name().ok())
+                        .and_then(|n| self.resource(resources, b"ExtGState", n))
+                        .and_then(|gs| gs.as_dict().ok());
+                    if let Some(lw) = gs.and_then(|gs| gs.get(b"LW").ok()) {
+                        state.line_width = number(lw)?;
+                    }
+                }
+                "G" | "RG" | "K" | "SC" | "SCN" => {
+                    if let Some(c) = color_from(&numbers(args).unwrap_or_default()) {
+                        state.stroke = Some(c);
+                    }
+                }
+                "g" | "rg" | "k" | "sc" | "scn" => {
+                    if let Some(c) = color_from(&numbers(args).unwrap_or_default()) {
+                        state.fill = Some(c);
+                    }
+                }
+                "CS" => state.stroke = Some(Color::from_rgb(0, 0, 0)),
+                "cs" => state.fill = Some(Color::from_rgb(0, 0, 0)),
+                "m" => {
+                    if let [x, y] = numbers(args)?[..] {
+                        cur = Point::from_xy(x, y);
+                        cmds.push(Cmd::MoveTo(state.ctm.apply(&cur)));
+                    }
+                }
+                "l" => {
+                    if let [x, y] = numbers(args)?[..] {
+                        cur = Point::from_xy(x, y);
+                        cmds.push(Cmd::LineTo(state.ctm.apply(&cur)));
+                    }
+                }
+                "c" | "v" | "y" => {
+                    let pts = numbers(args)?
+                        .chunks_exact(2)
+                        .map(|c| Point::from_xy(c[0], c[1]))
+                        .collect::<Vec<_>>();
+                    let (c1, c2, p) = match (op.operator.as_str(), pts.as_slice()) {
+                        ("c", &[c1, c2, p]) => (c1, c2, p),
+                        ("v", &[c2, p]) => (cur, c2, p),
+                        ("y", &[c1, p]) => (c1, p, p),
+                        _ => return Err(format!("invalid operands for {}", op.operator).into()),
+                    };
+                    cur = p;
+                    cmds.push(Cmd::CurveTo(
+                        state.ctm.apply(&c1),
+                        state.ctm.apply(&c2),
+                        state.ctm.apply(&p),
+                    ));
+                }
+                "h" => cmds.push(Cmd::Close),
+                "re" => {
+                    if let [x, y, w, h] = numbers(args)?[..] {
+                        let corners = [(x, y), (x + w, y), (x + w, y + h), (x, y + h)]
+                            .map(|(x, y)| state.ctm.apply(&Point::from_xy(x, y)));
+                        cmds.push(Cmd::MoveTo(corners[0]));
+                        cmds.extend(corners[1..].iter().map(|p| Cmd::LineTo(*p)));
+                        cmds.push(Cmd::Close);
+                        cur = Point::from_xy(x, y);
+                    }
+                }
+                "S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" | "n" => {
+                    let op = op.operator.as_str();
+                    if matches!(op, "s" | "b" | "b*") {
+                        cmds.push(Cmd::Close);
+                    }
+                    let stroked = matches!(op, "S" | "s" | "B" | "B*" | "b" | "b*");
+                    let filled = matches!(op, "f" | "F" | "f*" | "B" | "B*" | "b" | "b*");
+                    let path = Path::new(std::mem::take(&mut cmds));
+                    if !(stroked || filled) || path.is_empty() {
+                        continue;
+                    }
+                    let style = state.style(stroked, filled, &self.layers);
+                    if let Some(rule) = self.matcher.find(&style) {
+                        self.paths.push(Match {
+                            path: path.transform(|p| self.page.apply(p)),
+                            kind: rule.kind,
+                            stroke_width: style.stroke_width,
+                        });
+                    }
+                }
+                "BT" => {
+                    tm = Transform::identity();
+                    tlm = tm;
+                }
+                "ET" => self.end_text(&mut text),
+                "Tf" => {
+                    state.font = args.first().and_then(|n| n.as_name().ok()).map(Vec::from);
+                }
+                "TL" => state.leading = number(args.first().ok_or("no leading")?)?,
+                "Td" | "TD" | "Tm" | "T*" => {
+                    match (op.operator.as_str(), &numbers(args)?[..]) {
+                        ("Tm", &[a, b, c, d, e, f]) => tlm = Transform::new(a, b, c, d, e, f),
+                        ("Td", &[tx, ty]) => tlm = Transform::translate(tx, ty).then(&tlm),
+                        ("TD", &[tx, ty]) => {
+                            state.leading = -ty;
+                            tlm = Transform::translate(tx, ty).then(&tlm);
+                        }
+                        ("T*", _) => tlm = Transform::translate(0.0, -state.leading).then(&tlm),
+                        _ => return Err(format!("invalid operands for {}", op.operator).into()),
+                    }
+                    tm = tlm;
+                    self.end_text(&mut text);
+                }
+                "Tj" | "TJ" | "'" | "\"" => {
+                    if matches!(op.operator.as_str(), "'" | "\"") {
+                        tlm = Transform::translate(0.0, -state.leading).then(&tlm);
+                        tm = tlm;
+                        self.end_text(&mut text);
+                    }
+                    let strings = match args.last() {
+                        Some(Object::Array(items)) => items.iter().collect::<Vec<_>>(),
+                        Some(s) => vec![s],
+                        None => Vec::new(),
+                    };
+                    let (_, s) = text.get_or_insert_with(|| {
+                        let origin = tm.then(&state.ctm).apply(&Point::from_xy(0.0, 0.0));
+                        (self.page.apply(&origin), String::new())
+                    });
+                    for bytes in strings.iter().filter_map(|o| o.as_str().ok()) {
+                        s.push_str(&self.decode(resources, state.font.as_deref(), bytes));
+                    }
+                }
+                "BDC" => {
+                    let layer = self.layer_name(resources, args);
+                    self.marks.push(layer.is_some());
+                    if let Some(layer) = layer {
+                        self.layers.push(layer);
+                    }
+                }
+                "BMC" => self.marks.push(false),
+                "EMC" => {
+                    if let Some(true) = self.marks.pop() {
+                        self.layers.pop();
+                    }
+                }
+                "Do" => {
+                    let form = args
+                        .first()
+                        .and_then(|n| n.as_name().ok())
+                        .and_then(|n| self.resource(resources, b"XObject", n))
+                        .and_then(|o| o.as_stream().ok())
+                        .filter(|s| {
+                            s.dict.get(b"Subtype").and_then(|s| s.as_name()).ok() == Some(b"Form")
+                        });
+                    if let Some(form) = form {
+                        if self.depth >= MAX_FORM_DEPTH {
+                            return Err("form xobjects nested too deeply".into());
+                        }
+                        let mut inner = state.clone();
+                        if let Ok(m) = form.dict.get(b"Matrix").and_then(|m| m.as_array()) {
+                            if let [a, b, c, d, e, f] = numbers(m)?[..] {
+                                inner.ctm = Transform::new(a, b, c, d, e, f).then(&state.ctm);
+                            }
+                        }
+                        let inner_resources = form
+                            .dict
+                            .get(b"Resources")
+                            .ok()
+                            .and_then(|r| self.deref(r).ok())
+                            .and_then(|r| r.as_dict().ok())
+                            .or(resources);
+                        let data = form
+                            .decompressed_content()
+                            .unwrap_or_else(|_| form.content.clone());
+                        self.depth += 1;
+                        self.walk(&data, inner_resources, inner)?;
+                        self.depth -= 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.end_text(&mut text);
+        Ok(())
+    }
+}
+
+pub fn is_pdf<P: AsRef<path::Path>>(src: P) -> bool {
+    use std::io::Read;
+    let mut magic = [0u8; 5];
+    std::fs::File::open(src)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok()
+        && &magic == b"%PDF-"
+}
+
+// Walks the content stream of the given page (1-based) and returns every
+// painted path accepted by the matcher along with the elevation labels
+// shown as text. Coordinates are in page units with the origin at the
+// top-left of the media box, as the page is displayed once its /Rotate is
+// applied, matching pdftocairo's SVG.
+pub fn read_page<P: AsRef<path::Path>>(
+    src: P,
+    page: u32,
+    matcher: &Matcher,
+) -> Result<extract::Source, Box<dyn Error>> {
+    read_document(&Document::load(src)?, page, matcher)
+}
+
+fn read_document(
+    doc: &Document,
+    page: u32,
+    matcher: &Matcher,
+) -> Result<extract::Source, Box<dyn Error>> {
+    let page_id = *doc
+        .get_pages()
+        .get(&page)
+        .ok_or(format!("no page {}", page))?;
+
+    let media_box = match inherited(doc, page_id, b"MediaBox")? {
+        Some(mb) => match numbers(mb.as_array()?)?[..] {
+            [a, b, c, d] => [a, b, c, d],
+            _ => return Err("invalid media box".into()),
+        },
+        None => return Err("no media box".into()),
+    };
+    let [x0, y0, x1, y1] = media_box;
+    let (x0, x1) = (x0.min(x1), x0.max(x1));
+    let (y0, y1) = (y0.min(y1), y0.max(y1));
+    let (w, h) = (x1 - x0, y1 - y0);
+
+    // Pages are turned clockwise for display by /Rotate, a multiple of 90.
+    let rotate = match inherited(doc, page_id, b"Rotate")? {
+        Some(r) => r.as_i64()?.rem_euclid(360),
+        None => 0,
+    };
+    let (turn, size) = match rotate {
+        0 => (Transform::identity(), Size::new(w, h)),
+        90 => (Transform::new(0.0, 1.0, -1.0, 0.0, h, 0.0), Size::new(h, w)),
+        180 => (Transform::new(-1.0, 0.0, 0.0, -1.0, w, h), Size::new(w, h)),
+        270 => (Transform::new(0.0, -1.0, 1.0, 0.0, 0.0, w), Size::new(h, w)),
+        _ => return Err(format!("invalid page rotation: {}", rotate).into()),
+    };
+
+    let (resources, resource_ids) = doc.get_page_resources(page_id)?;
+    let resources = resources.or_else(|| {
+        resource_ids
+            .first()
+            .and_then(|id| doc.get_dictionary(*id).ok())
+    });
+
+    let mut walker = Walker {
+        doc,
+        matcher,
+        page: Transform::new(1.0, 0.0, 0.0, -1.0, -x0, y1).then(&turn),
+        paths: Vec::new(),
+        labels: Vec::new(),
+        layers: Vec::new(),
+        marks: Vec::new(),
+        depth: 0,
+    };
+    walker.walk(&doc.get_page_content(page_id), resources, State::default())?;
+
+    Ok(extract::Source {
+        size,
+        matches: walker.paths,
+        labels: walker.labels,
+    })
+}
+
+// Looks up a page attribute, which may be set on the page or inherited from
+// any of the page tree nodes above it.
+fn inherited<'a>(
+    doc: &'a Document,
+    page_id: lopdf::ObjectId,
+    key: &[u8],
+) -> Result<Option<&'a Object>, Box<dyn Error>> {
+    let mut node = doc.get_dictionary(page_id)?;
+    for _ in 0..MAX_TREE_DEPTH {
+        if let Ok(obj) = node.get(key) {
+            return Ok(Some(doc.dereference(obj)?.1));
+        }
+        match node.get(b"Parent") {
+            Ok(parent) => node = doc.get_dictionary(parent.as_reference()?)?,
+            Err(_) => return Ok(None),
+        }
+    }
+    Err("page tree nested too deeply".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A one page PDF, 200 units square, with the given content stream.
+    fn pdf(content: &str) -> Document {
+        let objects = [
+            String::from("<< /Type /Catalog /Pages 2 0 R >>"),
+            String::from("<< /Type /Pages /Kids [3 0 R] /Count 1 /MediaBox [0 0 200 200] >>"),
+            String::from("<< /Type /Page /Parent 2 0 R /Contents 4 0 R /Resources << >> >>"),
+            format!(
+                "<< /Length {} >>\nstream\n{}\nendstream",
+                content.len(),
+                content
+            ),
+        ];
+        let mut out = String::from("%PDF-1.4\n");
+        let mut offsets = Vec::new();
+        for (i, obj) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, obj));
+        }
+        let xref = out.len();
+        out.push_str(&format!(
+            "xref\n0 {}\n0000000000 65535 f \n",
+            objects.len() + 1
+        ));
+        for offset in offsets {
+            out.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        out.push_str(&format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        ));
+        Document::load_mem(out.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn scaled_widths_match_the_default_rule() {
+        // 9.9001 under a 0.1 scale is 0.99001 give or take rounding.
+        let doc = pdf("0.1 0 0 0.1 0 0 cm 0.698 0.698 0.698 RG 9.9001 w 100 100 m 1900 100 l S");
+        let source = read_document(&doc, 1, &Matcher::default()).unwrap();
+        assert_eq!(source.matches.len(), 1);
+        let m = &source.matches[0];
+        assert!((m.stroke_width.unwrap() - 0.99001).abs() < 1e-9);
+        let bounds = m.path.bounds();
+        assert_eq!(
+            (bounds.x(), bounds.y(), bounds.width(), bounds.height()),
+            (10.0, 190.0, 180.0, 0.0)
+        );
+    }
+}
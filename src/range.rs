@@ -1,5 +1,7 @@
 use std::{error::Error, fmt, str::FromStr};
 
+use serde::de;
+
 #[derive(Debug, Clone)]
 pub struct Range {
     r: std::ops::Range<f64>,
@@ -13,6 +15,17 @@ impl Range {
     pub fn to_std(&self) -> std::ops::Range<f64> {
         self.r.clone()
     }
+
+    pub fn includes(&self, v: f64) -> bool {
+        self.r.start <= v && v <= self.r.end
+    }
+
+    // Like includes, but lets v be off by a fraction of its size, for values
+    // that have picked up rounding error along the way.
+    pub fn includes_approx(&self, v: f64, tolerance: f64) -> bool {
+        let slack = tolerance * v.abs().max(self.r.start.abs()).max(self.r.end.abs());
+        self.r.start - slack <= v && v <= self.r.end + slack
+    }
 }
 
 impl From<std::ops::Range<f64>> for Range {
@@ -29,12 +42,59 @@ impl fmt::Display for Range {
 
 impl FromStr for Range {
     type Err = Box<dyn Error>;
+    // Either end can be negative, so each '-' is tried as the separator
+    // until both sides parse.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.split_once('-') {
-            Some((a, b)) => Ok(Range {
-                r: a.parse()?..b.parse()?,
-            }),
-            None => Err(format!("invalid range: {}", s).into()),
-        }
+        s.match_indices('-')
+            .find_map(|(i, _)| Some(s[..i].parse().ok()?..s[i + 1..].parse().ok()?))
+            .map(|r| Range { r })
+            .ok_or_else(|| format!("invalid range: {}", s).into())
+    }
+}
+
+impl<'d> de::Deserialize<'d> for Range {
+    fn deserialize<D>(de: D) -> Result<Range, D::Error>
+    where
+        D: de::Deserializer<'d>,
+    {
+        de.deserialize_any(RangeVisitor)
+    }
+}
+
+struct RangeVisitor;
+
+impl<'d> de::Visitor<'d> for RangeVisitor {
+    type Value = Range;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a range string or a number")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Range::from_str(v).map_err(|e| E::custom(e.to_string()))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Range::from(v..v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.visit_f64(v as f64)
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.visit_f64(v as f64)
     }
 }
@@ -2,6 +2,7 @@ use std::str::FromStr;
 
 use super::Point;
 
+#[derive(Debug, Clone, Copy)]
 pub struct Rect {
     top_left: Point,
     bottom_right: Point,
@@ -38,11 +39,47 @@ impl Rect {
         self.bottom_right.y() - self.top_left.y()
     }
 
+    pub fn from_points(a: &Point, b: &Point) -> Self {
+        Self {
+            top_left: Point::from_xy(a.x().min(b.x()), a.y().min(b.y())),
+            bottom_right: Point::from_xy(a.x().max(b.x()), a.y().max(b.y())),
+        }
+    }
+
+    pub fn expand(&self, d: f64) -> Self {
+        Self {
+            top_left: Point::from_xy(self.top_left.x() - d, self.top_left.y() - d),
+            bottom_right: Point::from_xy(self.bottom_right.x() + d, self.bottom_right.y() + d),
+        }
+    }
+
+    pub fn contains(&self, p: &Point) -> bool {
+        self.top_left.x() <= p.x()
+            && p.x() <= self.bottom_right.x()
+            && self.top_left.y() <= p.y()
+            && p.y() <= self.bottom_right.y()
+    }
+
+    // Rects that only touch intersect, so that the zero area bounds of a
+    // straight line still hit.
     pub fn intersects(&self, other: &Rect) -> bool {
-        self.top_left.x() < other.bottom_right.x()
-            && self.bottom_right.x() > other.top_left.x()
-            && self.top_left.y() < other.bottom_right.y()
-            && self.bottom_right.y() > other.top_left.y()
+        self.top_left.x() <= other.bottom_right.x()
+            && self.bottom_right.x() >= other.top_left.x()
+            && self.top_left.y() <= other.bottom_right.y()
+            && self.bottom_right.y() >= other.top_left.y()
+    }
+
+    pub fn union(&self, other: &Rect) -> Rect {
+        Self {
+            top_left: Point::from_xy(
+                self.top_left.x().min(other.top_left.x()),
+                self.top_left.y().min(other.top_left.y()),
+            ),
+            bottom_right: Point::from_xy(
+                self.bottom_right.x().max(other.bottom_right.x()),
+                self.bottom_right.y().max(other.bottom_right.y()),
+            ),
+        }
     }
 }
 
@@ -1,6 +1,6 @@
-use std::{error::Error, fs, path::PathBuf};
+use std::{error::Error, path::PathBuf};
 
-use crate::{extract, topo, Range, Seed, Size, ThemeRef};
+use crate::{extract, topo, Format, Themes};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -10,48 +10,33 @@ pub struct Args {
     #[clap()]
     dst: String,
 
-    #[clap(long, default_value_t = Default::default(), value_parser = Seed::from_arg)]
-    seed: Seed,
+    #[clap(flatten)]
+    view: topo::ViewArgs,
 
-    #[clap(long, default_value_t = Size::new(1600.0,600.0), value_parser = Size::from_arg)]
-    size: Size,
+    #[clap(flatten)]
+    location: topo::LocationArgs,
 
-    #[clap(long, value_parser=Range::from_arg, default_value_t=Range::from(1.0..8.0))]
-    scale_range: Range,
-
-    #[clap(long, value_parser=Range::from_arg, default_value_t=Range::from(2.0..4.0))]
-    line_width_range: Range,
-
-    #[clap(long, default_value_t=ThemeRef::from_path("themes.bin"), value_parser=ThemeRef::from_arg)]
-    theme: ThemeRef,
-
-    #[clap(long)]
-    hide_location: bool,
+    #[clap(long, value_parser=Format::from_arg)]
+    format: Option<Format>,
 }
 
 impl topo::Options for Args {
-    fn seed(&self) -> &Seed {
-        &self.seed
-    }
-
-    fn size(&self) -> &Size {
-        &self.size
-    }
-
-    fn scale_range(&self) -> std::ops::Range<f64> {
-        self.scale_range.to_std()
+    fn view(&self) -> &topo::ViewArgs {
+        &self.view
     }
 
-    fn line_width_range(&self) -> std::ops::Range<f64> {
-        self.line_width_range.to_std()
+    fn location(&self) -> Option<&topo::LocationArgs> {
+        Some(&self.location)
     }
 
-    fn theme(&self) -> &ThemeRef {
-        &self.theme
+    fn themes(&self) -> Option<&Themes> {
+        None
     }
 
-    fn hide_location(&self) -> bool {
-        self.hide_location
+    fn format(&self) -> Format {
+        self.format
+            .or_else(|| Format::from_path(&self.dst))
+            .unwrap_or(Format::Png)
     }
 
     fn dest(&self) -> PathBuf {
@@ -60,16 +45,9 @@ impl topo::Options for Args {
 }
 
 pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
-    let data: extract::Data = serde_json::from_reader(&mut fs::File::open(&args.src)?)?;
-    topo::render(&data, args, |theme, origin, scale, seed| {
-        println!(
-            "theme = {}, origin = ({:0.2}, {:0.2}), scale = {:0.2}, seed = {}",
-            theme,
-            origin.x(),
-            origin.y(),
-            scale,
-            seed
-        );
+    let data = extract::Data::open(&args.src)?;
+    topo::render(&data, args, |params| {
+        println!("{}", params);
         Ok(())
     })
 }
@@ -1,8 +1,18 @@
-use std::{error::Error, fs, path::PathBuf};
+use std::{
+    error::Error,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
 
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::{extract, topo, Range, Seed, Size, ThemeRef};
+use crate::{extract, gallery, topo, Format, Seed, Themes};
 
 #[derive(clap::Args, Debug)]
 pub struct Args {
@@ -15,76 +25,182 @@ pub struct Args {
     #[clap(long, default_value_t = 10)]
     n: usize,
 
-    #[clap(long, default_value_t = Size::new(1600.0,600.0), value_parser = Size::from_arg)]
-    size: Size,
+    #[clap(long)]
+    jobs: Option<usize>,
+
+    #[clap(flatten)]
+    view: topo::ViewArgs,
 
-    #[clap(long, value_parser=Range::from_arg, default_value_t=Range::from(1.0..8.0))]
-    scale_range: Range,
+    #[clap(flatten)]
+    location: topo::LocationArgs,
 
-    #[clap(long, value_parser=Range::from_arg, default_value_t=Range::from(2.0..4.0))]
-    line_width_range: Range,
+    #[clap(long, default_value_t = Format::Png, value_parser=Format::from_arg)]
+    format: Format,
 
-    #[clap(long, default_value_t=ThemeRef::from_path("themes.bin"), value_parser=ThemeRef::from_arg)]
-    theme: ThemeRef,
+    #[clap(long)]
+    contact_sheet: bool,
 
     #[clap(long)]
-    hide_location: bool,
+    gallery: bool,
+
+    #[clap(long, default_value_t = 320.0)]
+    thumb_width: f64,
+
+    #[clap(long, default_value_t = 4)]
+    columns: usize,
+}
+
+/// What a batch rendered, written to manifest.json alongside the images.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub seed: Seed,
+    pub renders: Vec<Entry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub file: String,
+    #[serde(flatten)]
+    pub params: topo::Params,
 }
 
 struct Options<'a> {
     args: &'a Args,
+    themes: &'a Themes,
     seed: Seed,
     dest: PathBuf,
 }
 
 impl<'a> topo::Options for Options<'a> {
-    fn seed(&self) -> &Seed {
-        &self.seed
-    }
-
-    fn size(&self) -> &Size {
-        &self.args.size
-    }
-
-    fn scale_range(&self) -> std::ops::Range<f64> {
-        self.args.scale_range.to_std()
+    fn view(&self) -> &topo::ViewArgs {
+        &self.args.view
     }
 
-    fn line_width_range(&self) -> std::ops::Range<f64> {
-        self.args.line_width_range.to_std()
+    fn location(&self) -> Option<&topo::LocationArgs> {
+        Some(&self.args.location)
     }
 
-    fn theme(&self) -> &ThemeRef {
-        &self.args.theme
+    fn themes(&self) -> Option<&Themes> {
+        Some(self.themes)
     }
 
-    fn hide_location(&self) -> bool {
-        self.args.hide_location
+    fn format(&self) -> Format {
+        self.args.format
     }
 
     fn dest(&self) -> PathBuf {
         self.dest.clone()
     }
+
+    // Each render has its own seed, drawn from the batch's.
+    fn seed(&self) -> &Seed {
+        &self.seed
+    }
 }
 
 pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
-    let data: extract::Data = serde_json::from_reader(&mut fs::File::open(&args.src)?)?;
+    if args.contact_sheet && args.format != Format::Png {
+        return Err("contact sheets can only be made from png renders".into());
+    }
+    if args.thumb_width <= 0.0 {
+        return Err("thumb width must be positive".into());
+    }
+
+    let data = extract::Data::open(&args.src)?;
 
     let dst = PathBuf::from(&args.dst_dir);
     if !dst.exists() {
         fs::create_dir_all(&dst)?;
     }
 
-    let mut rng = Seed::default().rng();
-    for _ in 0..args.n {
-        let seed = Seed::new(rng.gen::<u64>());
-        let options = Options {
-            args,
-            seed,
-            dest: dst.join(format!("{}.png", seed)),
-        };
-        topo::render(&data, &options, |_, _, _, _| Ok(()))?;
+    // The seeds are all drawn before rendering starts so that each image
+    // depends only on the master seed and its position in the batch, not on
+    // which worker happens to render it.
+    let mut rng = args.view.seed.rng();
+    let seeds = (0..args.n)
+        .map(|_| Seed::new(rng.gen::<u64>()))
+        .collect::<Vec<_>>();
+
+    let themes = args.view.theme.open()?;
+    let jobs = match args.jobs {
+        Some(0) => return Err("jobs must be at least 1".into()),
+        Some(jobs) => jobs,
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+
+    // Workers take the next seed until they run out or one of them fails.
+    let next = AtomicUsize::new(0);
+    let failure = Mutex::new(None);
+    let params = Mutex::new(vec![None; seeds.len()]);
+    thread::scope(|s| {
+        for _ in 0..jobs.min(seeds.len()) {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&seed) = seeds.get(i) else {
+                    break;
+                };
+                let options = Options {
+                    args,
+                    themes: &themes,
+                    seed,
+                    dest: dst.join(format!("{}.{}", seed, args.format.extension())),
+                };
+                let rendered = topo::render(&data, &options, |p| {
+                    params.lock().unwrap()[i] = Some(p.clone());
+                    Ok(())
+                });
+                if let Err(e) = rendered {
+                    failure
+                        .lock()
+                        .unwrap()
+                        .get_or_insert_with(|| format!("{}: {}", seed, e));
+                    next.store(seeds.len(), Ordering::Relaxed);
+                }
+            });
+        }
+    });
+
+    if let Some(e) = failure.into_inner()? {
+        return Err(e.into());
     }
 
+    let renders = seeds
+        .iter()
+        .zip(params.into_inner()?)
+        .map(|(seed, params)| Entry {
+            file: format!("{}.{}", seed, args.format.extension()),
+            params: params.unwrap(),
+        })
+        .collect();
+    let manifest = Manifest {
+        seed: args.view.seed,
+        renders,
+    };
+    serde_json::to_writer_pretty(fs::File::create(dst.join("manifest.json"))?, &manifest)?;
+
+    if args.contact_sheet {
+        gallery::contact_sheet(
+            &dst,
+            &manifest,
+            &args.view.size,
+            args.location.location_format,
+            args.thumb_width,
+            args.columns,
+            &dst.join("contact-sheet.png"),
+        )?;
+    }
+    if args.gallery {
+        gallery::html(
+            &manifest,
+            args.location.location_format,
+            args.thumb_width,
+            &dst.join("index.html"),
+        )?;
+    }
+    println!(
+        "seed = {}, {} renders",
+        args.view.seed,
+        manifest.renders.len()
+    );
     Ok(())
 }
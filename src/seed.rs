@@ -1,5 +1,6 @@
 use rand::{rngs::SmallRng, Rng, SeedableRng};
-use std::{num::ParseIntError, str::FromStr};
+use serde::{de, ser};
+use std::{fmt, num::ParseIntError, str::FromStr};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct Seed {
@@ -21,8 +22,8 @@ impl FromStr for Seed {
     }
 }
 
-impl std::fmt::Display for Seed {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:016x}", self.v)
     }
 }
@@ -43,4 +44,47 @@ impl Seed {
     pub fn rng(&self) -> SmallRng {
         SmallRng::seed_from_u64(self.v)
     }
+
+    // A seed for an independent stream of randomness, mixed with splitmix64
+    // so that nearby seeds and streams don't give related values.
+    pub fn derive(&self, stream: u64) -> Seed {
+        let mut z = self.v ^ stream.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Seed::new(z ^ (z >> 31))
+    }
+}
+
+impl ser::Serialize for Seed {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        ser.serialize_str(&self.to_string())
+    }
+}
+
+impl<'d> de::Deserialize<'d> for Seed {
+    fn deserialize<D>(de: D) -> Result<Seed, D::Error>
+    where
+        D: de::Deserializer<'d>,
+    {
+        de.deserialize_str(SeedVisitor)
+    }
+}
+
+struct SeedVisitor;
+
+impl<'d> de::Visitor<'d> for SeedVisitor {
+    type Value = Seed;
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Seed::from_str(v).map_err(|e| E::custom(e.to_string()))
+    }
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a hex seed")
+    }
 }
@@ -0,0 +1,179 @@
+use std::{
+    error::Error,
+    fs,
+    io::{Cursor, Read, Seek, SeekFrom},
+    path,
+};
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use serde_json::{Map, Value};
+
+use crate::Point;
+
+const FILE_CODE: i32 = 9994;
+
+/// A polyline or polygon from a shapefile along with its attributes from the
+/// accompanying dBASE file, if there is one.
+#[derive(Debug)]
+pub struct Shape {
+    pub parts: Vec<Vec<Point>>,
+    pub closed: bool,
+    pub z: Option<f64>,
+    pub attributes: Map<String, Value>,
+}
+
+// Reads the polylines and polygons of a .shp file. Other kinds of shapes,
+// like points, are skipped since they can't be contours.
+pub fn read(src: &str) -> Result<Vec<Shape>, Box<dyn Error>> {
+    let mut r = Cursor::new(fs::read(src)?);
+    if r.read_i32::<BigEndian>()? != FILE_CODE {
+        return Err(format!("{} is not a shapefile", src).into());
+    }
+    let len = r.get_ref().len() as u64;
+    r.seek(SeekFrom::Start(100))?;
+
+    let mut shapes = Vec::new();
+    while r.position() + 8 <= len {
+        let _number = r.read_i32::<BigEndian>()?;
+        // The content length is in 16-bit words.
+        let words = u64::try_from(r.read_i32::<BigEndian>()?)
+            .map_err(|_| "invalid shapefile record length")?;
+        let end = r.position() + 2 * words;
+        if end > len {
+            return Err("shapefile is truncated".into());
+        }
+        shapes.push(read_shape(&mut r)?);
+        r.seek(SeekFrom::Start(end))?;
+    }
+
+    let dbf = path::Path::new(src).with_extension("dbf");
+    if dbf.exists() {
+        for (shape, attributes) in shapes.iter_mut().zip(read_dbf(&dbf)?) {
+            if let Some(shape) = shape {
+                shape.attributes = attributes;
+            }
+        }
+    }
+
+    Ok(shapes.into_iter().flatten().collect())
+}
+
+fn read_shape(r: &mut Cursor<Vec<u8>>) -> Result<Option<Shape>, Box<dyn Error>> {
+    let kind = r.read_i32::<LittleEndian>()?;
+    let (closed, has_z) = match kind {
+        3 => (false, false),
+        5 => (true, false),
+        13 | 23 => (false, kind == 13),
+        15 | 25 => (true, kind == 15),
+        _ => return Ok(None),
+    };
+
+    r.seek(SeekFrom::Current(32))?; // bounding box
+    let count = |n: i32| usize::try_from(n).map_err(|_| "invalid shape");
+    let num_parts = count(r.read_i32::<LittleEndian>()?)?;
+    let num_points = count(r.read_i32::<LittleEndian>()?)?;
+    // Each part's start is 4 bytes and each point 16, and the counts are
+    // checked against what's left of the file before they're read.
+    let room = (r.get_ref().len() as u64).saturating_sub(r.position());
+    if num_parts as u64 * 4 + num_points as u64 * 16 > room {
+        return Err("shapefile is truncated".into());
+    }
+    let starts = (0..num_parts)
+        .map(|_| r.read_i32::<LittleEndian>().map(|i| i as usize))
+        .collect::<Result<Vec<_>, _>>()?;
+    let points = (0..num_points)
+        .map(|_| {
+            Ok(Point::from_xy(
+                r.read_f64::<LittleEndian>()?,
+                r.read_f64::<LittleEndian>()?,
+            ))
+        })
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+
+    let z = if has_z && num_points > 0 {
+        r.seek(SeekFrom::Current(16))?; // z range
+        Some(r.read_f64::<LittleEndian>()?)
+    } else {
+        None
+    };
+
+    let parts = starts
+        .iter()
+        .zip(starts.iter().skip(1).chain([&num_points]))
+        .map(|(&start, &end)| {
+            points
+                .get(start..end)
+                .map(|p| p.to_vec())
+                .ok_or("invalid shape part")
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Some(Shape {
+        parts,
+        closed,
+        z,
+        attributes: Map::new(),
+    }))
+}
+
+struct Field {
+    name: String,
+    kind: u8,
+    len: usize,
+}
+
+// Reads the records of a dBASE table, keeping numeric fields as numbers and
+// everything else as trimmed strings.
+fn read_dbf(src: &path::Path) -> Result<Vec<Map<String, Value>>, Box<dyn Error>> {
+    let mut r = Cursor::new(fs::read(src)?);
+    r.seek(SeekFrom::Start(4))?;
+    let num_records = r.read_u32::<LittleEndian>()? as usize;
+    let header_len = r.read_u16::<LittleEndian>()? as u64;
+    let record_len = r.read_u16::<LittleEndian>()? as usize;
+
+    let mut fields = Vec::new();
+    r.seek(SeekFrom::Start(32))?;
+    while r.position() + 32 <= header_len {
+        let mut desc = [0u8; 32];
+        r.read_exact(&mut desc)?;
+        if desc[0] == 0x0d {
+            break;
+        }
+        let name = desc[..11].split(|&b| b == 0).next().unwrap_or_default();
+        fields.push(Field {
+            name: String::from_utf8_lossy(name).trim().to_owned(),
+            kind: desc[11],
+            len: desc[16] as usize,
+        });
+    }
+
+    // The record count comes from the file, so it's checked against the
+    // records there's room for before anything is allocated for them.
+    let room = (r.get_ref().len() as u64).saturating_sub(header_len);
+    if num_records as u64 * record_len.max(1) as u64 > room {
+        return Err("dbf file is truncated".into());
+    }
+
+    r.seek(SeekFrom::Start(header_len))?;
+    let mut record = vec![0u8; record_len];
+    let mut records = Vec::with_capacity(num_records);
+    for _ in 0..num_records {
+        r.read_exact(&mut record)?;
+        let mut attributes = Map::new();
+        let mut offset = 1; // deletion flag
+        for field in &fields {
+            let raw = record
+                .get(offset..offset + field.len)
+                .ok_or("invalid dbf record")?;
+            let text = String::from_utf8_lossy(raw).trim().to_owned();
+            let value = match field.kind {
+                b'N' | b'F' => text.parse::<f64>().map(Value::from).unwrap_or(Value::Null),
+                _ => Value::String(text),
+            };
+            attributes.insert(field.name.clone(), value);
+            offset += field.len;
+        }
+        records.push(attributes);
+    }
+    Ok(records)
+}
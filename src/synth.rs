@@ -0,0 +1,191 @@
+use std::{error::Error, fmt, fs, path::PathBuf, str::FromStr};
+
+use rand::Rng;
+
+use crate::{extract, geo, grid::Grid, noise::Noise, topo, Format, Size, Themes};
+
+// Keeps the terrain's random stream apart from the composition's, which
+// comes straight from the seed.
+const TERRAIN_STREAM: u64 = 0x7465_7272_6169_6e00;
+
+// Synthetic terrain isn't anywhere, so it is placed at 0°N 0°E with about ten
+// meters to a pixel to give it a plausible georeference.
+const DEGREES_PER_PIXEL: f64 = 10.0 / 111_320.0;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap()]
+    dst: String,
+
+    #[clap(flatten)]
+    view: topo::ViewArgs,
+
+    #[clap(long, value_parser=Format::from_arg)]
+    format: Option<Format>,
+
+    #[clap(long, value_parser=Terrain::from_arg, default_value_t)]
+    terrain: Terrain,
+
+    #[clap(long, default_value_t = Size::new(4000.0,4000.0), value_parser = Size::from_arg)]
+    map_size: Size,
+
+    #[clap(long, default_value_t = 4.0)]
+    resolution: f64,
+
+    #[clap(long, default_value_t = 3.0)]
+    frequency: f64,
+
+    #[clap(long, default_value_t = 6)]
+    octaves: usize,
+
+    #[clap(long, default_value_t = 1000.0)]
+    relief: f64,
+
+    #[clap(long, default_value_t = 20.0)]
+    interval: f64,
+
+    #[clap(long, default_value_t = 5)]
+    index_every: usize,
+
+    #[clap(long)]
+    save: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Terrain {
+    #[default]
+    Fbm,
+    Ridged,
+    Warped,
+}
+
+impl Terrain {
+    pub fn from_arg(s: &str) -> Result<Self, String> {
+        s.parse().map_err(|_| format!("invalid terrain: {}", s))
+    }
+}
+
+impl FromStr for Terrain {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fbm" => Ok(Terrain::Fbm),
+            "ridged" => Ok(Terrain::Ridged),
+            "warped" => Ok(Terrain::Warped),
+            _ => Err(format!("unknown terrain: {}", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for Terrain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terrain::Fbm => write!(f, "fbm"),
+            Terrain::Ridged => write!(f, "ridged"),
+            Terrain::Warped => write!(f, "warped"),
+        }
+    }
+}
+
+impl topo::Options for Args {
+    fn view(&self) -> &topo::ViewArgs {
+        &self.view
+    }
+
+    // There's no real place to center on or label.
+    fn location(&self) -> Option<&topo::LocationArgs> {
+        None
+    }
+
+    fn themes(&self) -> Option<&Themes> {
+        None
+    }
+
+    fn format(&self) -> Format {
+        self.format
+            .or_else(|| Format::from_path(&self.dst))
+            .unwrap_or(Format::Png)
+    }
+
+    fn dest(&self) -> PathBuf {
+        PathBuf::from(&self.dst)
+    }
+}
+
+// Samples the terrain over the map every resolution pixels, stretched to
+// span zero to relief.
+fn heightfield(args: &Args) -> Grid {
+    let mut rng = args.view.seed.derive(TERRAIN_STREAM).rng();
+    let noise = Noise::new(&mut rng);
+    let (ox, oy) = (rng.gen_range(0.0..256.0), rng.gen_range(0.0..256.0));
+
+    let cols = (args.map_size.width() / args.resolution).ceil() as usize + 1;
+    let rows = (args.map_size.height() / args.resolution).ceil() as usize + 1;
+    let span = args.map_size.width().max(args.map_size.height());
+    let mut values = Vec::with_capacity(cols * rows);
+    for row in 0..rows {
+        for col in 0..cols {
+            let x = ox + col as f64 * args.resolution / span * args.frequency;
+            let y = oy + row as f64 * args.resolution / span * args.frequency;
+            values.push(match args.terrain {
+                Terrain::Fbm => noise.fbm(x, y, args.octaves),
+                Terrain::Ridged => noise.ridged(x, y, args.octaves),
+                Terrain::Warped => noise.warped(x, y, args.octaves),
+            });
+        }
+    }
+
+    let (lo, hi) = values
+        .iter()
+        .fold((f64::MAX, f64::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    let k = if hi > lo {
+        args.relief / (hi - lo)
+    } else {
+        0.0
+    };
+    Grid::new(
+        cols,
+        rows,
+        values.into_iter().map(|v| (v - lo) * k).collect(),
+    )
+}
+
+pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    if args.resolution <= 0.0 || args.interval <= 0.0 {
+        return Err("resolution and interval must be positive".into());
+    }
+
+    let grid = heightfield(args);
+    let size = Size::new(
+        (grid.width() - 1) as f64 * args.resolution,
+        (grid.height() - 1) as f64 * args.resolution,
+    );
+    let region = geo::Rect::new(
+        geo::LatLng::new(0.0, 0.0),
+        geo::LatLng::new(
+            -size.height() * DEGREES_PER_PIXEL,
+            size.width() * DEGREES_PER_PIXEL,
+        ),
+    );
+    let data = extract::Data::new(
+        size,
+        region,
+        geo::Projection::Equirectangular,
+        None,
+        grid.to_contours(
+            args.interval,
+            args.index_every,
+            (args.resolution, args.resolution),
+        ),
+    );
+
+    if let Some(path) = &args.save {
+        serde_json::to_writer(&mut fs::File::create(path)?, &data)?;
+    }
+
+    topo::render(&data, args, |params| {
+        println!("{}", params);
+        Ok(())
+    })
+}
@@ -13,6 +13,7 @@ use rand::{
     RngCore,
 };
 use regex::Regex;
+use serde::{de, ser};
 
 use crate::Color;
 
@@ -68,22 +69,35 @@ pub struct ThemeRef {
 
 impl ThemeRef {
     pub fn pick(&self, rng: &mut dyn RngCore) -> Result<(ThemeRef, Vec<Color>), Box<dyn Error>> {
+        Ok(self.pick_from(&self.open()?, rng))
+    }
+
+    // Picks from themes that are already open, which should be the ones this
+    // refers to.
+    pub fn pick_from(&self, themes: &Themes, rng: &mut dyn RngCore) -> (ThemeRef, Vec<Color>) {
         match self.index {
-            Some(idx) => Ok((self.clone(), Themes::open(&self.path)?.get(idx))),
+            Some(idx) => (self.clone(), themes.get(idx)),
             None => {
-                let themes = Themes::open(&self.path)?;
                 let (idx, colors) = themes.pick(rng);
-                Ok((
+                (
                     Self {
                         path: self.path.clone(),
                         index: Some(idx),
                     },
                     colors,
-                ))
+                )
             }
         }
     }
 
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    pub fn open(&self) -> io::Result<Themes> {
+        Themes::open(&self.path)
+    }
+
     pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
         ThemeRef {
             path: PathBuf::from(path.as_ref()),
@@ -125,3 +139,37 @@ impl fmt::Display for ThemeRef {
         }
     }
 }
+
+impl ser::Serialize for ThemeRef {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        ser.serialize_str(&self.to_string())
+    }
+}
+
+impl<'d> de::Deserialize<'d> for ThemeRef {
+    fn deserialize<D>(de: D) -> Result<ThemeRef, D::Error>
+    where
+        D: de::Deserializer<'d>,
+    {
+        de.deserialize_str(ThemeRefVisitor)
+    }
+}
+
+struct ThemeRefVisitor;
+
+impl<'d> de::Visitor<'d> for ThemeRefVisitor {
+    type Value = ThemeRef;
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        ThemeRef::from_str(v).map_err(|e| E::custom(e.to_string()))
+    }
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a theme")
+    }
+}
@@ -0,0 +1,329 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::{extract, Path, Point, Rect, Size};
+
+// How close, as a fraction of the map's smaller side, the end of an open
+// contour must be to the border to be closed along it.
+const BORDER_TOLERANCE: f64 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fill {
+    // Only the contour lines are drawn.
+    #[default]
+    None,
+    // Only the bands between the contours are drawn.
+    Bands,
+    // The bands are drawn with the contour lines over them.
+    Both,
+}
+
+impl Fill {
+    pub fn from_arg(s: &str) -> Result<Self, String> {
+        s.parse().map_err(|_| format!("invalid fill: {}", s))
+    }
+
+    pub fn has_bands(&self) -> bool {
+        matches!(self, Fill::Bands | Fill::Both)
+    }
+
+    pub fn has_lines(&self) -> bool {
+        matches!(self, Fill::None | Fill::Both)
+    }
+}
+
+impl FromStr for Fill {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Fill::None),
+            "bands" => Ok(Fill::Bands),
+            "both" => Ok(Fill::Both),
+            _ => Err(format!("unknown fill: {}", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for Fill {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fill::None => write!(f, "none"),
+            Fill::Bands => write!(f, "bands"),
+            Fill::Both => write!(f, "both"),
+        }
+    }
+}
+
+/// The map divided into bands of elevation by its contours. Each contour is
+/// closed into a polygon, open ones along the border of the map, and the
+/// polygons are nested by containment so that filling them in order, outer
+/// ones first, paints every area between contours with its own band.
+///
+/// Bands are numbered from 0, below the lowest contour, to `levels`, above
+/// the highest.
+#[derive(Debug, Default)]
+pub struct Tints {
+    pub levels: usize,
+    pub root: usize,
+    pub polygons: Vec<Polygon>,
+}
+
+#[derive(Debug)]
+pub struct Polygon {
+    pub path: Path,
+    pub bounds: Rect,
+    pub band: usize,
+}
+
+struct Ring {
+    points: Vec<Point>,
+    // A point on the contour itself, never on the border.
+    inside: Point,
+    bounds: Rect,
+    area: f64,
+    level: usize,
+    children: Vec<usize>,
+}
+
+impl Tints {
+    pub fn new(data: &extract::Data) -> Self {
+        let mut elevations = data
+            .contours
+            .iter()
+            .filter_map(|c| c.elevation)
+            .collect::<Vec<_>>();
+        elevations.sort_by(f64::total_cmp);
+        elevations.dedup();
+        if elevations.is_empty() {
+            return Self::default();
+        }
+
+        let border = Border::new(&data.size);
+        let mut rings = Vec::new();
+        for contour in &data.contours {
+            let Some(e) = contour.elevation else {
+                continue;
+            };
+            let level = elevations.partition_point(|&v| v < e);
+            for line in contour.path.flatten() {
+                if let Some(ring) = border.close(line, level) {
+                    rings.push(ring);
+                }
+            }
+        }
+
+        // Contours don't cross and open ones are all closed on the side away
+        // from the same corner, so any two rings are either nested or apart.
+        // Inserting the largest first means a ring's parent is always there.
+        let mut order = (0..rings.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| rings[b].area.total_cmp(&rings[a].area));
+        let mut top = Vec::new();
+        for &i in &order {
+            let mut parent: Option<usize> = None;
+            loop {
+                let siblings = parent.map_or(&top, |p| &rings[p].children);
+                match siblings.iter().find(|&&j| contains(&rings[j], &rings[i])) {
+                    Some(&j) => parent = Some(j),
+                    None => break,
+                }
+            }
+            match parent {
+                Some(p) => rings[p].children.push(i),
+                None => top.push(i),
+            }
+        }
+
+        // Crossing a contour moves to the band on its other side, so the
+        // bands all follow from the band outside every ring. That is taken to
+        // be whichever agrees best with the rest.
+        let levels = elevations.len();
+        let root = (0..=levels)
+            .min_by_key(|&root| assign(&rings, &top, root).1)
+            .unwrap_or(0);
+        let (bands, _) = assign(&rings, &top, root);
+
+        let mut polygons = Vec::with_capacity(rings.len());
+        let mut queue = top.clone();
+        let mut next = 0;
+        while let Some(&i) = queue.get(next) {
+            next += 1;
+            queue.extend(&rings[i].children);
+            polygons.push(Polygon {
+                path: Path::from_points(&rings[i].points, true),
+                bounds: rings[i].bounds,
+                band: bands[i],
+            });
+        }
+
+        Self {
+            levels,
+            root,
+            polygons,
+        }
+    }
+}
+
+// The band inside each ring, given the band outside all of them, along with
+// how many times a ring didn't border the band it was crossed from.
+fn assign(rings: &[Ring], top: &[usize], root: usize) -> (Vec<usize>, usize) {
+    let mut bands = vec![0; rings.len()];
+    let mut mismatches = 0;
+    let mut stack = top.iter().map(|&i| (i, root)).collect::<Vec<_>>();
+    while let Some((i, outside)) = stack.pop() {
+        let level = rings[i].level;
+        let band = if outside == level {
+            level + 1
+        } else if outside == level + 1 {
+            level
+        } else {
+            mismatches += 1;
+            if outside <= level {
+                level + 1
+            } else {
+                level
+            }
+        };
+        bands[i] = band;
+        stack.extend(rings[i].children.iter().map(|&c| (c, band)));
+    }
+    (bands, mismatches)
+}
+
+fn contains(outer: &Ring, inner: &Ring) -> bool {
+    let (a, b) = (&outer.bounds, &inner.bounds);
+    a.x() <= b.x()
+        && a.y() <= b.y()
+        && b.x() + b.width() <= a.x() + a.width()
+        && b.y() + b.height() <= a.y() + a.height()
+        && is_inside(&outer.points, &inner.inside)
+}
+
+// Even-odd test of whether p is inside the polygon.
+fn is_inside(points: &[Point], p: &Point) -> bool {
+    let mut inside = false;
+    let mut j = points.len() - 1;
+    for (i, a) in points.iter().enumerate() {
+        let b = &points[j];
+        if (a.y() > p.y()) != (b.y() > p.y())
+            && p.x() < (b.x() - a.x()) * (p.y() - a.y()) / (b.y() - a.y()) + a.x()
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn area(points: &[Point]) -> f64 {
+    let n = points.len();
+    (0..n)
+        .map(|i| {
+            let (a, b) = (&points[i], &points[(i + 1) % n]);
+            a.x() * b.y() - b.x() * a.y()
+        })
+        .sum::<f64>()
+        .abs()
+        / 2.0
+}
+
+// The edge of the map, measured clockwise from its top left corner.
+struct Border {
+    width: f64,
+    height: f64,
+    tolerance: f64,
+}
+
+impl Border {
+    fn new(size: &Size) -> Self {
+        Self {
+            width: size.width(),
+            height: size.height(),
+            tolerance: BORDER_TOLERANCE * size.width().min(size.height()),
+        }
+    }
+
+    // Turns a contour line into a ring. Lines that end apart are closed
+    // along the border, on the side away from the top left corner, and are
+    // dropped if they don't end on the border.
+    fn close(&self, mut line: Vec<Point>, level: usize) -> Option<Ring> {
+        if line.len() < 2 {
+            return None;
+        }
+        let inside = line[line.len() / 2];
+        let (first, last) = (line[0], line[line.len() - 1]);
+        if first.distance_to(&last) <= f64::EPSILON * (self.width + self.height) {
+            line.pop();
+        } else {
+            let (from, to) = (self.snap(&last)?, self.snap(&first)?);
+            *line.last_mut().unwrap() = self.at(from);
+            line[0] = self.at(to);
+            let corners = [
+                self.width,
+                self.width + self.height,
+                2.0 * self.width + self.height,
+            ];
+            if from < to {
+                line.extend(
+                    corners
+                        .iter()
+                        .filter(|&&c| from < c && c < to)
+                        .map(|&c| self.at(c)),
+                );
+            } else {
+                line.extend(
+                    corners
+                        .iter()
+                        .rev()
+                        .filter(|&&c| to < c && c < from)
+                        .map(|&c| self.at(c)),
+                );
+            }
+        }
+        if line.len() < 3 {
+            return None;
+        }
+
+        let bounds = line
+            .iter()
+            .fold(Rect::from_points(&line[0], &line[0]), |r, p| {
+                r.union(&Rect::from_points(p, p))
+            });
+        Some(Ring {
+            area: area(&line),
+            points: line,
+            inside,
+            bounds,
+            level,
+            children: Vec::new(),
+        })
+    }
+
+    // How far along the border the point nearest p is, if p is close to it.
+    fn snap(&self, p: &Point) -> Option<f64> {
+        let (w, h) = (self.width, self.height);
+        let (x, y) = (p.x().clamp(0.0, w), p.y().clamp(0.0, h));
+        [
+            (y, x),
+            (w - x, w + y),
+            (h - y, 2.0 * w + h - x),
+            (x, 2.0 * (w + h) - y),
+        ]
+        .into_iter()
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .filter(|(d, _)| p.distance_to(&Point::from_xy(x, y)) + d <= self.tolerance)
+        .map(|(_, t)| t)
+    }
+
+    fn at(&self, t: f64) -> Point {
+        let (w, h) = (self.width, self.height);
+        if t <= w {
+            Point::from_xy(t, 0.0)
+        } else if t <= w + h {
+            Point::from_xy(w, t - w)
+        } else if t <= 2.0 * w + h {
+            Point::from_xy(2.0 * w + h - t, h)
+        } else {
+            Point::from_xy(0.0, 2.0 * (w + h) - t)
+        }
+    }
+}
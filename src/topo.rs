@@ -1,110 +1,601 @@
-use std::{error::Error, fs, path::PathBuf};
+use std::{error::Error, fmt, fs, path::PathBuf};
 
-use cairo::{Context, FontSlant, FontWeight, Format, ImageSurface};
+use cairo::{Context, FontSlant, FontWeight, ImageSurface, PdfSurface, SvgSurface};
 use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
 
-use crate::{extract, geo, Color, Point, Seed, Size, ThemeRef};
+use crate::{
+    elevation::is_multiple,
+    extract::{self, Contour, Kind},
+    geo, Color, Fill, Format, Point, Range, Rect, Seed, Size, ThemeRef, Themes,
+};
+
+/// Everything that was chosen for a render, which together with the options
+/// is enough to render it again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Params {
+    pub seed: Seed,
+    pub theme: ThemeRef,
+    pub origin: Point,
+    pub scale: f64,
+    #[serde(default)]
+    pub rotation: f64,
+    pub line_width: f64,
+    pub location: geo::LatLng,
+}
+
+impl fmt::Display for Params {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "theme = {}, origin = ({:0.2}, {:0.2}), scale = {:0.2}, rotation = {:0.2}, line width = {:0.2}, location = {}, seed = {}",
+            self.theme,
+            self.origin.x(),
+            self.origin.y(),
+            self.scale,
+            self.rotation,
+            self.line_width,
+            self.location,
+            self.seed
+        )
+    }
+}
 
 pub fn render<O, F>(data: &extract::Data, opts: &O, f: F) -> Result<(), Box<dyn Error>>
 where
     O: Options,
-    F: Fn(&ThemeRef, &Point, f64, &Seed) -> Result<(), Box<dyn Error>>,
+    F: Fn(&Params) -> Result<(), Box<dyn Error>>,
 {
     let extract::Data {
         size,
         region,
-        paths,
+        contours,
+        ..
     } = data;
 
     let mut rng = opts.seed().rng();
 
-    let tx = rng.gen_range(0.0..size.width() - opts.size().width());
-    let ty = rng.gen_range(0.0..size.height() - opts.size().height());
-    let scale = rng.gen_range(opts.scale_range());
-    let (theme, colors) = opts.theme().pick(&mut rng)?;
+    let step = |c: &&Contour| match opts.elevation_step() {
+        Some(step) => c.elevation.is_some_and(|e| is_multiple(e, step)),
+        None => true,
+    };
 
-    f(&theme, &Point::from_xy(tx, ty), scale, opts.seed())?;
+    if size.width() <= 0.0 || size.height() <= 0.0 {
+        return Err(format!("the map has no area ({})", size).into());
+    }
+    if opts.size().width() <= 0.0 || opts.size().height() <= 0.0 {
+        return Err(format!("the output has no area ({})", opts.size()).into());
+    }
+    let georef = data.georef()?;
+    let scales = opts.scale_range();
+    if !(scales.start > 0.0 && scales.start <= scales.end) {
+        return Err(format!(
+            "the scale range ({}-{}) must be positive and increasing",
+            scales.start, scales.end
+        )
+        .into());
+    }
+    if opts.scale().is_some_and(|s| s <= 0.0) {
+        return Err("the scale must be positive".into());
+    }
+    if let Some(r) = opts.rotation_range().filter(|r| r.start > r.end) {
+        return Err(format!(
+            "the rotation range ({}-{}) must be increasing",
+            r.start, r.end
+        )
+        .into());
+    }
 
-    let (bg, fg) = select_color_pair(&mut rng, &colors);
+    // Framings are drawn until one is dense enough, so that a seed always
+    // settles on the same one.
+    let mut attempts = 0;
+    let framing = loop {
+        attempts += 1;
+        let fx = rng.gen_range(0.0..1.0);
+        let fy = rng.gen_range(0.0..1.0);
+        let scale = sample(&mut rng, opts.scale_range());
+        let rotation = opts.rotation_range().map(|r| sample(&mut rng, r));
 
-    let paths = paths
-        .iter()
-        .map(|path| path.transform(|p| Point::from_xy((p.x() - tx) * scale, (p.y() - ty) * scale)));
-
-    // yolo, don't care if it's a little wrong.
-    let location = geo::LatLng::new(
-        lerp(&(region.se.lat..region.nw.lat), ty / size.height()),
-        lerp(&(region.nw.lng..region.se.lng), tx / size.width()),
-    );
-
-    let surface = ImageSurface::create(
-        Format::ARgb32,
-        opts.size().width() as i32,
-        opts.size().height() as i32,
-    )?;
-    let ctx = Context::new(&surface)?;
-
-    bg.set(&ctx);
-    ctx.rectangle(0.0, 0.0, opts.size().width(), opts.size().height());
-    ctx.fill()?;
-
-    ctx.save()?;
-    let lw = lerp(
-        &opts.line_width_range(),
-        inv_lerp(&opts.scale_range(), scale),
-    );
-    ctx.set_line_width(lw);
-    for path in paths {
-        ctx.new_path();
-        path.draw(&ctx);
-        fg.set(&ctx);
-        ctx.stroke()?;
-    }
-    ctx.restore()?;
-
-    if !opts.hide_location() {
-        ctx.save()?;
-        let label = format!("{}", location);
-        ctx.select_font_face("Helvetica Neue", FontSlant::Normal, FontWeight::Normal);
-        ctx.set_font_size(24.0);
-        let exts = ctx.text_extents(&label)?;
-        bg.set(&ctx);
-        ctx.rectangle(
-            opts.size().width() - exts.width() - 30.0,
-            opts.size().height() + exts.y_bearing() - 30.0,
-            exts.width() + 20.0,
-            exts.height() + 20.0,
-        );
-        ctx.fill()?;
+        // Explicit parameters replace the random ones only after they have been
+        // drawn so the rest of the composition still follows from the seed.
+        let rotation = opts.rotation().or(rotation).unwrap_or(0.0);
+
+        // A random scale that would show more than the whole map is raised
+        // until the view fits on it.
+        let scale = match opts.scale() {
+            Some(scale) => scale,
+            None => scale.max(fit_scale(size, opts.size(), rotation)),
+        };
+        let (w, h) = (opts.size().width(), opts.size().height());
+        let origin = match opts.center() {
+            Some(center) => {
+                let c = georef.to_point(center);
+                if !(0.0..=size.width()).contains(&c.x()) || !(0.0..=size.height()).contains(&c.y())
+                {
+                    return Err(format!("{} is outside of {}", center, region).into());
+                }
+                Point::from_xy(c.x() - w / (2.0 * scale), c.y() - h / (2.0 * scale))
+            }
+            // The view is placed so that its bounds are on the map, which
+            // leaves no empty corners when it is turned. When a given scale
+            // shows more than the map, the view is centered on it instead.
+            None => {
+                let (ex, ey) = half_extents(opts.size(), scale, rotation);
+                let place = |f: f64, e: f64, len: f64| {
+                    if 2.0 * e > len {
+                        len / 2.0
+                    } else {
+                        e + f * (len - 2.0 * e)
+                    }
+                };
+                Point::from_xy(
+                    place(fx, ex, size.width()) - w / (2.0 * scale),
+                    place(fy, ey, size.height()) - h / (2.0 * scale),
+                )
+            }
+        };
+        let framing = Framing {
+            origin,
+            scale,
+            rotation,
+        };
 
-        fg.set(&ctx);
-        ctx.move_to(
-            opts.size().width() - exts.width() - 20.0,
-            opts.size().height() - 20.0,
+        let min = match opts.min_density() {
+            Some(min) => min,
+            None => break framing,
+        };
+        let density = density(
+            data,
+            &framing.view(opts.size()),
+            opts,
+            line_width(opts, scale),
+            scale,
+            step,
         );
-        ctx.show_text(&label)?;
+        if density >= min {
+            break framing;
+        }
+        // Drawing again can't change a framing that was given in full.
+        if opts.center().is_some()
+            && opts.scale().is_some()
+            && (opts.rotation().is_some() || opts.rotation_range().is_none())
+        {
+            return Err(format!(
+                "the framing given by --center and --scale has a density of {:.3}, below the minimum of {}",
+                density, min
+            )
+            .into());
+        }
+        if attempts >= opts.max_attempts() {
+            return Err(format!(
+                "no framing had a density of at least {} after {} attempts",
+                min, attempts
+            )
+            .into());
+        }
+    };
+    let Framing {
+        origin,
+        scale,
+        rotation,
+    } = framing;
+
+    let (theme, colors) = match opts.themes() {
+        Some(themes) => opts.theme().pick_from(themes, &mut rng),
+        None => opts.theme().pick(&mut rng)?,
+    };
+
+    let (bg, fg) = select_color_pair(&mut rng, &colors);
+
+    // The ramp spans every contour on the map, not just the visible ones, so
+    // colors stay the same wherever the map is cropped.
+    let ramp = if opts.color_by_elevation() {
+        elevation_range(&contours.iter().filter(step).collect::<Vec<_>>())
+            .map(|r| (r, ramp_colors(&colors, &bg)))
+    } else {
+        None
+    };
+
+    let lw = line_width(opts, scale);
+
+    let tints = if opts.fill().has_bands() {
+        let tints = data.tints();
+        if tints.levels == 0 {
+            return Err("filling bands needs contours with elevations".into());
+        }
+        Some((tints, band_colors(&colors, &bg, &fg)))
+    } else {
+        None
+    };
+
+    // The part of the map in view, padded by the widest stroke so that lines
+    // just outside still draw their edges.
+    let view = framing
+        .view(opts.size())
+        .expand(lw.max(lw * opts.index_line_width_scale()) / scale);
+    let contours = data
+        .index()
+        .query(&view)
+        .into_iter()
+        .map(|i| &contours[i])
+        .filter(step)
+        .collect::<Vec<_>>();
+
+    let location = georef.to_latlng(&framing.center(opts.size()));
+
+    f(&Params {
+        seed: *opts.seed(),
+        theme,
+        origin,
+        scale,
+        rotation,
+        line_width: lw,
+        location: location.clone(),
+    })?;
+
+    let draw = |ctx: &Context| -> Result<(), Box<dyn Error>> {
+        bg.set(ctx);
+        ctx.rectangle(0.0, 0.0, opts.size().width(), opts.size().height());
         ctx.fill()?;
+
+        ctx.save()?;
+        if rotation != 0.0 {
+            let (w, h) = (opts.size().width(), opts.size().height());
+            ctx.translate(w / 2.0, h / 2.0);
+            ctx.rotate(rotation.to_radians());
+            ctx.translate(-w / 2.0, -h / 2.0);
+        }
+        let to_output =
+            |p: &Point| Point::from_xy((p.x() - origin.x()) * scale, (p.y() - origin.y()) * scale);
+
+        // Each polygon is filled over the ones that contain it, so the bands
+        // are painted from the outside in, starting with the whole map.
+        if let Some((tints, colors)) = &tints {
+            let band = |b: usize| color_at(colors, b as f64 / tints.levels as f64);
+            let (tl, br) = (
+                to_output(&Point::from_xy(0.0, 0.0)),
+                to_output(&Point::from_xy(size.width(), size.height())),
+            );
+            band(tints.root).set(ctx);
+            ctx.rectangle(tl.x(), tl.y(), br.x() - tl.x(), br.y() - tl.y());
+            ctx.fill()?;
+            for polygon in &tints.polygons {
+                if !view.intersects(&polygon.bounds) {
+                    continue;
+                }
+                ctx.new_path();
+                polygon.path.transform(to_output).draw(ctx);
+                band(polygon.band).set(ctx);
+                ctx.fill()?;
+            }
+        }
+
+        let contours = if opts.fill().has_lines() {
+            &contours[..]
+        } else {
+            &[]
+        };
+        for contour in contours {
+            let Some(path) = contour.path.clip(&view) else {
+                continue;
+            };
+            let path = path.transform(to_output);
+            ctx.new_path();
+            path.draw(ctx);
+            match (&ramp, contour.elevation) {
+                (Some((range, colors)), Some(e)) => color_at(colors, inv_lerp(range, e)).set(ctx),
+                _ => fg.set(ctx),
+            }
+            ctx.set_line_width(match contour.kind {
+                Kind::Index => lw * opts.index_line_width_scale(),
+                Kind::Intermediate => lw,
+            });
+            ctx.stroke()?;
+        }
         ctx.restore()?;
-    }
 
-    surface.write_to_png(&mut fs::File::create(opts.dest())?)?;
+        if !opts.hide_location() {
+            ctx.save()?;
+            let label = format!("{}", location.display(opts.location_notation()));
+            ctx.select_font_face("Helvetica Neue", FontSlant::Normal, FontWeight::Normal);
+            ctx.set_font_size(24.0);
+            let exts = ctx.text_extents(&label)?;
+            bg.set(ctx);
+            ctx.rectangle(
+                opts.size().width() - exts.width() - 30.0,
+                opts.size().height() + exts.y_bearing() - 30.0,
+                exts.width() + 20.0,
+                exts.height() + 20.0,
+            );
+            ctx.fill()?;
+
+            fg.set(ctx);
+            ctx.move_to(
+                opts.size().width() - exts.width() - 20.0,
+                opts.size().height() - 20.0,
+            );
+            ctx.show_text(&label)?;
+            ctx.fill()?;
+            ctx.restore()?;
+        }
+        Ok(())
+    };
+
+    let (w, h) = (opts.size().width(), opts.size().height());
+    match opts.format() {
+        Format::Png => {
+            let surface = ImageSurface::create(cairo::Format::ARgb32, w as i32, h as i32)?;
+            draw(&Context::new(&surface)?)?;
+            surface.write_to_png(&mut fs::File::create(opts.dest())?)?;
+        }
+        Format::Svg => {
+            let surface = SvgSurface::new(w, h, Some(opts.dest()))?;
+            draw(&Context::new(&surface)?)?;
+            surface.finish();
+            surface.status()?;
+        }
+        Format::Pdf => {
+            let surface = PdfSurface::new(w, h, opts.dest())?;
+            draw(&Context::new(&surface)?)?;
+            surface.finish();
+            surface.status()?;
+        }
+    }
     Ok(())
 }
 
-pub trait Options {
-    fn seed(&self) -> &Seed;
+// The options shared by every command that renders a map.
+#[derive(clap::Args, Debug)]
+pub struct ViewArgs {
+    #[clap(long, default_value_t = Default::default(), value_parser = Seed::from_arg)]
+    pub seed: Seed,
+
+    #[clap(long, default_value_t = Size::new(1600.0,600.0), value_parser = Size::from_arg)]
+    pub size: Size,
+
+    #[clap(long, value_parser=Range::from_arg, default_value_t=Range::from(1.0..8.0))]
+    pub scale_range: Range,
+
+    #[clap(long)]
+    pub scale: Option<f64>,
+
+    #[clap(long, value_parser=Range::from_arg, default_value_t=Range::from(2.0..4.0))]
+    pub line_width_range: Range,
+
+    #[clap(long, default_value_t=ThemeRef::from_path("themes.bin"), value_parser=ThemeRef::from_arg)]
+    pub theme: ThemeRef,
+
+    #[clap(long, default_value_t = 2.0)]
+    pub index_line_width_scale: f64,
+
+    #[clap(long)]
+    pub color_by_elevation: bool,
+
+    #[clap(long, value_parser=positive)]
+    pub elevation_step: Option<f64>,
+
+    #[clap(long, value_parser=Fill::from_arg, default_value_t)]
+    pub fill: Fill,
+
+    #[clap(long, allow_negative_numbers = true)]
+    pub rotation: Option<f64>,
+
+    #[clap(long, value_parser=Range::from_arg, allow_hyphen_values = true)]
+    pub rotation_range: Option<Range>,
 
-    fn size(&self) -> &Size;
+    #[clap(long)]
+    pub min_density: Option<f64>,
 
-    fn scale_range(&self) -> std::ops::Range<f64>;
+    #[clap(long, default_value_t = 20)]
+    pub max_attempts: usize,
+}
+
+fn positive(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v > 0.0 => Ok(v),
+        _ => Err(format!("{} is not a positive number", s)),
+    }
+}
+
+// The options for rendering a map of a real place.
+#[derive(clap::Args, Debug)]
+pub struct LocationArgs {
+    #[clap(long, value_parser=geo::LatLng::from_arg)]
+    pub center: Option<geo::LatLng>,
+
+    #[clap(long)]
+    pub hide_location: bool,
+
+    #[clap(long, default_value_t = geo::Notation::Dms, value_parser=geo::Notation::from_arg)]
+    pub location_format: geo::Notation,
+}
 
-    fn line_width_range(&self) -> std::ops::Range<f64>;
+pub trait Options {
+    fn view(&self) -> &ViewArgs;
+
+    // Maps that aren't of anywhere in particular have no location options,
+    // so they can't be centered and their location isn't shown.
+    fn location(&self) -> Option<&LocationArgs>;
 
-    fn theme(&self) -> &ThemeRef;
+    // The theme's file when it is already open, otherwise it is opened for
+    // each render.
+    fn themes(&self) -> Option<&Themes>;
 
-    fn hide_location(&self) -> bool;
+    fn format(&self) -> Format;
 
     fn dest(&self) -> PathBuf;
+
+    fn seed(&self) -> &Seed {
+        &self.view().seed
+    }
+
+    fn size(&self) -> &Size {
+        &self.view().size
+    }
+
+    fn scale_range(&self) -> std::ops::Range<f64> {
+        self.view().scale_range.to_std()
+    }
+
+    fn scale(&self) -> Option<f64> {
+        self.view().scale
+    }
+
+    fn center(&self) -> Option<&geo::LatLng> {
+        self.location().and_then(|l| l.center.as_ref())
+    }
+
+    fn line_width_range(&self) -> std::ops::Range<f64> {
+        self.view().line_width_range.to_std()
+    }
+
+    fn theme(&self) -> &ThemeRef {
+        &self.view().theme
+    }
+
+    fn hide_location(&self) -> bool {
+        self.location().is_none_or(|l| l.hide_location)
+    }
+
+    fn location_notation(&self) -> geo::Notation {
+        self.location()
+            .map_or_else(geo::Notation::default, |l| l.location_format)
+    }
+
+    fn index_line_width_scale(&self) -> f64 {
+        self.view().index_line_width_scale
+    }
+
+    fn color_by_elevation(&self) -> bool {
+        self.view().color_by_elevation
+    }
+
+    fn elevation_step(&self) -> Option<f64> {
+        self.view().elevation_step
+    }
+
+    fn fill(&self) -> Fill {
+        self.view().fill
+    }
+
+    fn min_density(&self) -> Option<f64> {
+        self.view().min_density
+    }
+
+    fn rotation(&self) -> Option<f64> {
+        self.view().rotation
+    }
+
+    fn rotation_range(&self) -> Option<std::ops::Range<f64>> {
+        self.view().rotation_range.as_ref().map(Range::to_std)
+    }
+
+    fn max_attempts(&self) -> usize {
+        self.view().max_attempts
+    }
+}
+
+// Where the output lies on the map: the point at its top left before it is
+// turned, how much the map is scaled up and how many degrees the map is
+// turned clockwise about the center of the output.
+struct Framing {
+    origin: Point,
+    scale: f64,
+    rotation: f64,
+}
+
+impl Framing {
+    fn center(&self, size: &Size) -> Point {
+        Point::from_xy(
+            self.origin.x() + size.width() / (2.0 * self.scale),
+            self.origin.y() + size.height() / (2.0 * self.scale),
+        )
+    }
+
+    // The part of the map that the output covers, or the bounds of it when
+    // the view is turned.
+    fn view(&self, size: &Size) -> Rect {
+        if self.rotation == 0.0 {
+            return Rect::new(
+                self.origin,
+                Point::from_xy(
+                    self.origin.x() + size.width() / self.scale,
+                    self.origin.y() + size.height() / self.scale,
+                ),
+            );
+        }
+        let c = self.center(size);
+        let (ex, ey) = half_extents(size, self.scale, self.rotation);
+        Rect::new(
+            Point::from_xy(c.x() - ex, c.y() - ey),
+            Point::from_xy(c.x() + ex, c.y() + ey),
+        )
+    }
+}
+
+// Half the width and height, on the map, of the bounds of an output of size
+// turned by rotation degrees.
+fn half_extents(size: &Size, scale: f64, rotation: f64) -> (f64, f64) {
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let (sin, cos) = (sin.abs(), cos.abs());
+    (
+        (cos * size.width() + sin * size.height()) / (2.0 * scale),
+        (sin * size.width() + cos * size.height()) / (2.0 * scale),
+    )
+}
+
+// The smallest scale at which all of an output of size turned by rotation
+// degrees lies on the map.
+fn fit_scale(map: &Size, size: &Size, rotation: f64) -> f64 {
+    let (ex, ey) = half_extents(size, 1.0, rotation);
+    (2.0 * ex / map.width()).max(2.0 * ey / map.height())
+}
+
+// Draws from r, which may hold a single value.
+fn sample(rng: &mut dyn RngCore, r: std::ops::Range<f64>) -> f64 {
+    if r.is_empty() {
+        r.start
+    } else {
+        rng.gen_range(r)
+    }
+}
+
+fn line_width<O: Options>(opts: &O, scale: f64) -> f64 {
+    lerp(
+        &opts.line_width_range(),
+        inv_lerp(&opts.scale_range(), scale).clamp(0.0, 1.0),
+    )
+}
+
+// Roughly the fraction of the output that the strokes of the contours in
+// view would cover, ignoring where they cross.
+fn density<O, P>(data: &extract::Data, view: &Rect, opts: &O, lw: f64, scale: f64, step: P) -> f64
+where
+    O: Options,
+    P: Fn(&&Contour) -> bool,
+{
+    let ink = data
+        .index()
+        .query(view)
+        .into_iter()
+        .map(|i| &data.contours[i])
+        .filter(step)
+        .map(|c| {
+            let width = match c.kind {
+                Kind::Index => lw * opts.index_line_width_scale(),
+                Kind::Intermediate => lw,
+            };
+            let length = c.path.clip(view).map_or(0.0, |p| {
+                p.flatten()
+                    .iter()
+                    .flat_map(|l| l.windows(2))
+                    .map(|w| w[0].distance_to(&w[1]))
+                    .sum::<f64>()
+            });
+            length * scale * width
+        })
+        .sum::<f64>();
+    ink / (view.width() * view.height() * scale * scale)
 }
 
 fn select_color_pair(rng: &mut dyn RngCore, colors: &[Color]) -> (Color, Color) {
@@ -129,10 +620,55 @@ fn select_color_pair(rng: &mut dyn RngCore, colors: &[Color]) -> (Color, Color)
     }
 }
 
+// The theme's colors ordered by luminance moving away from the background,
+// which is left out so the lowest contours stay visible.
+fn ramp_colors(colors: &[Color], bg: &Color) -> Vec<Color> {
+    let mut ramp = colors.to_vec();
+    ramp.sort_by(|a, b| a.luminance().total_cmp(&b.luminance()));
+    if ramp.first().map(|c| c.luminance()) == Some(bg.luminance()) {
+        ramp.remove(0);
+    } else {
+        ramp.pop();
+        ramp.reverse();
+    }
+    ramp
+}
+
+// The theme's colors other than the one for lines, from the background's end
+// of the ramp for the lowest band to the other end for the highest.
+fn band_colors(colors: &[Color], bg: &Color, fg: &Color) -> Vec<Color> {
+    let mut ramp = colors.to_vec();
+    ramp.sort_by(|a, b| a.luminance().total_cmp(&b.luminance()));
+    if let Some(ix) = ramp.iter().position(|c| c.luminance() == fg.luminance()) {
+        ramp.remove(ix);
+    }
+    if ramp.last().map(|c| c.luminance()) == Some(bg.luminance()) {
+        ramp.reverse();
+    }
+    ramp
+}
+
+fn color_at(ramp: &[Color], t: f64) -> Color {
+    let t = t.clamp(0.0, 1.0) * (ramp.len() - 1) as f64;
+    let ix = (t.floor() as usize).min(ramp.len().saturating_sub(2));
+    ramp[ix].lerp(&ramp[(ix + 1).min(ramp.len() - 1)], t - ix as f64)
+}
+
+fn elevation_range(contours: &[&Contour]) -> Option<std::ops::Range<f64>> {
+    let elevations = contours.iter().filter_map(|c| c.elevation);
+    let min = elevations.clone().min_by(|a, b| a.total_cmp(b))?;
+    let max = elevations.max_by(|a, b| a.total_cmp(b))?;
+    Some(if min < max { min..max } else { min..min + 1.0 })
+}
+
 fn lerp(r: &std::ops::Range<f64>, v: f64) -> f64 {
     r.start * (1.0 - v) + r.end * v
 }
 
 fn inv_lerp(r: &std::ops::Range<f64>, v: f64) -> f64 {
-    (v - r.start) / (r.end - r.start)
+    if r.is_empty() {
+        0.0
+    } else {
+        (v - r.start) / (r.end - r.start)
+    }
 }
@@ -0,0 +1,240 @@
+use std::{error::Error, str::FromStr};
+
+use crate::Point;
+
+// An affine transform in the SVG sense, mapping (x, y) to
+// (a*x + c*y + e, b*x + d*y + f).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+    f: f64,
+}
+
+impl Transform {
+    pub fn new(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Self {
+        Self { a, b, c, d, e, f }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+    }
+
+    pub fn translate(tx: f64, ty: f64) -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, tx, ty)
+    }
+
+    pub fn scale(sx: f64, sy: f64) -> Self {
+        Self::new(sx, 0.0, 0.0, sy, 0.0, 0.0)
+    }
+
+    pub fn rotate(deg: f64) -> Self {
+        let (sin, cos) = deg.to_radians().sin_cos();
+        Self::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    pub fn skew_x(deg: f64) -> Self {
+        Self::new(1.0, 0.0, deg.to_radians().tan(), 1.0, 0.0, 0.0)
+    }
+
+    pub fn skew_y(deg: f64) -> Self {
+        Self::new(1.0, deg.to_radians().tan(), 0.0, 1.0, 0.0, 0.0)
+    }
+
+    pub fn coefficients(&self) -> [f64; 6] {
+        [self.a, self.b, self.c, self.d, self.e, self.f]
+    }
+
+    // Returns the transform that applies self and then next.
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            a: next.a * self.a + next.c * self.b,
+            b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d,
+            d: next.b * self.c + next.d * self.d,
+            e: next.a * self.e + next.c * self.f + next.e,
+            f: next.b * self.e + next.d * self.f + next.f,
+        }
+    }
+
+    pub fn apply(&self, p: &Point) -> Point {
+        Point::from_xy(
+            self.a * p.x() + self.c * p.y() + self.e,
+            self.b * p.x() + self.d * p.y() + self.f,
+        )
+    }
+
+    pub fn invert(&self) -> Option<Transform> {
+        let det = self.a * self.d - self.b * self.c;
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        Some(Transform {
+            a: self.d / det,
+            b: -self.b / det,
+            c: -self.c / det,
+            d: self.a / det,
+            e: (self.c * self.f - self.d * self.e) / det,
+            f: (self.b * self.e - self.a * self.f) / det,
+        })
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::identity()
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl FromStr for Transform {
+    type Err = Box<dyn Error>;
+
+    // Parses an SVG transform list such as "translate(10 20) scale(2)".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tx = Transform::identity();
+        let mut rest = s.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        while !rest.is_empty() {
+            let (name, tail) = rest
+                .split_once('(')
+                .ok_or(format!("invalid transform: {}", s))?;
+            let (args, tail) = tail
+                .split_once(')')
+                .ok_or(format!("invalid transform: {}", s))?;
+            let args = args
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|v| !v.is_empty())
+                .map(|v| v.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let t = match (name.trim(), args.as_slice()) {
+                ("matrix", &[a, b, c, d, e, f]) => Transform::new(a, b, c, d, e, f),
+                ("translate", &[tx]) => Transform::translate(tx, 0.0),
+                ("translate", &[tx, ty]) => Transform::translate(tx, ty),
+                ("scale", &[s]) => Transform::scale(s, s),
+                ("scale", &[sx, sy]) => Transform::scale(sx, sy),
+                ("rotate", &[a]) => Transform::rotate(a),
+                ("rotate", &[a, cx, cy]) => Transform::translate(-cx, -cy)
+                    .then(&Transform::rotate(a))
+                    .then(&Transform::translate(cx, cy)),
+                ("skewX", &[a]) => Transform::skew_x(a),
+                ("skewY", &[a]) => Transform::skew_y(a),
+                _ => return Err(format!("invalid transform: {}", s).into()),
+            };
+
+            // Within a list, the rightmost transform is applied first.
+            tx = t.then(&tx);
+            rest = tail.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        }
+        Ok(tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: &Point, b: &Point) -> bool {
+        a.distance_to(b) < 1e-9
+    }
+
+    fn maps(tx: &Transform, from: (f64, f64), to: (f64, f64)) {
+        let p = tx.apply(&Point::from_xy(from.0, from.1));
+        assert!(
+            close(&p, &Point::from_xy(to.0, to.1)),
+            "{:?} -> {}",
+            from,
+            p
+        );
+    }
+
+    #[test]
+    fn parses_each_transform() {
+        let cases = [
+            (
+                "matrix(1 2 3 4 5 6)",
+                Transform::new(1.0, 2.0, 3.0, 4.0, 5.0, 6.0),
+            ),
+            ("translate(10)", Transform::translate(10.0, 0.0)),
+            ("translate(10, -20)", Transform::translate(10.0, -20.0)),
+            ("scale(2)", Transform::scale(2.0, 2.0)),
+            ("scale(2 3)", Transform::scale(2.0, 3.0)),
+            ("rotate(90)", Transform::rotate(90.0)),
+            ("skewX(45)", Transform::skew_x(45.0)),
+            ("skewY(45)", Transform::skew_y(45.0)),
+            ("", Transform::identity()),
+        ];
+        for (s, want) in cases {
+            assert_eq!(s.parse::<Transform>().unwrap(), want, "{}", s);
+        }
+    }
+
+    #[test]
+    fn rotates_about_a_center() {
+        let tx = "rotate(90 10 10)".parse::<Transform>().unwrap();
+        maps(&tx, (10.0, 10.0), (10.0, 10.0));
+        maps(&tx, (20.0, 10.0), (10.0, 20.0));
+    }
+
+    #[test]
+    fn applies_lists_right_to_left() {
+        // The scale applies first, then the translation.
+        let tx = "translate(10 20) scale(2)".parse::<Transform>().unwrap();
+        maps(&tx, (1.0, 1.0), (12.0, 22.0));
+        let tx = "scale(2),translate(10 20)".parse::<Transform>().unwrap();
+        maps(&tx, (1.0, 1.0), (22.0, 42.0));
+    }
+
+    #[test]
+    fn then_applies_self_first() {
+        let tx = Transform::scale(2.0, 2.0).then(&Transform::translate(10.0, 0.0));
+        maps(&tx, (1.0, 1.0), (12.0, 2.0));
+        let tx = Transform::translate(10.0, 0.0).then(&Transform::scale(2.0, 2.0));
+        maps(&tx, (1.0, 1.0), (22.0, 2.0));
+        assert_eq!(tx.then(&Transform::identity()), tx);
+    }
+
+    #[test]
+    fn invert_undoes_the_transform() {
+        let tx = "translate(3 -4) rotate(30) skewX(10) scale(2 5)"
+            .parse::<Transform>()
+            .unwrap();
+        let inv = tx.invert().unwrap();
+        for (x, y) in [(0.0, 0.0), (1.0, 2.0), (-7.5, 3.25)] {
+            let p = Point::from_xy(x, y);
+            assert!(close(&inv.apply(&tx.apply(&p)), &p));
+        }
+        let [a, b, c, d, e, f] = tx.then(&inv).coefficients();
+        for (v, want) in [(a, 1.0), (b, 0.0), (c, 0.0), (d, 1.0), (e, 0.0), (f, 0.0)] {
+            assert!((v - want).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn singular_transforms_have_no_inverse() {
+        assert!(Transform::scale(0.0, 1.0).invert().is_none());
+        assert!(Transform::new(1.0, 2.0, 2.0, 4.0, 0.0, 0.0)
+            .invert()
+            .is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_lists() {
+        for s in [
+            "scale",
+            "scale(1",
+            "scale(1 2 3)",
+            "spin(1)",
+            "rotate(1 2)",
+            "scale(x)",
+        ] {
+            assert!(s.parse::<Transform>().is_err(), "{}", s);
+        }
+    }
+}